@@ -0,0 +1,392 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::crypto::{DataKey, SealedData};
+
+/// How long the client waits for the agent before falling back to the passphrase.
+const SOCKET_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Request {
+    Status,
+    Lock,
+    Decrypt { payload: String },
+    Encrypt { store: Value },
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Response {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    store: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed: Option<SealedData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    locks_in: Option<u64>,
+}
+
+/// The socket of the agent serving `data_file`, kept next to it like the lock file.
+pub fn agent_socket_path(data_file: &Path) -> PathBuf {
+    let name = data_file
+        .file_name()
+        .and_then(|value| value.to_str())
+        .unwrap_or("ttt.json");
+    data_file.with_file_name(format!("{}.agent.sock", name))
+}
+
+pub struct AgentClient {
+    socket: PathBuf,
+}
+
+impl AgentClient {
+    /// A client for the agent of `data_file`, if its socket exists.
+    pub fn find(data_file: &Path) -> Option<Self> {
+        let socket = agent_socket_path(data_file);
+        (cfg!(unix) && socket.exists()).then_some(AgentClient { socket })
+    }
+
+    pub fn decrypt(&self, payload: &str) -> Result<Value, String> {
+        let request = Request::Decrypt {
+            payload: payload.to_string(),
+        };
+        self.request(&request)?
+            .store
+            .ok_or_else(|| "The agent returned no data.".to_string())
+    }
+
+    pub fn encrypt(&self, store: &Value) -> Result<SealedData, String> {
+        let request = Request::Encrypt {
+            store: store.clone(),
+        };
+        self.request(&request)?
+            .sealed
+            .ok_or_else(|| "The agent returned no data.".to_string())
+    }
+
+    /// Time left until the agent forgets the key.
+    pub fn status(&self) -> Result<Duration, String> {
+        let locks_in = self.request(&Request::Status)?.locks_in.unwrap_or(0);
+        Ok(Duration::from_secs(locks_in))
+    }
+
+    pub fn lock(&self) -> Result<(), String> {
+        self.request(&Request::Lock).map(|_| ())
+    }
+
+    #[cfg(unix)]
+    fn request(&self, request: &Request) -> Result<Response, String> {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixStream;
+
+        let error = |err: std::io::Error| format!("Could not reach the agent: {}", err);
+        let mut stream = UnixStream::connect(&self.socket).map_err(error)?;
+        stream
+            .set_read_timeout(Some(SOCKET_TIMEOUT))
+            .map_err(error)?;
+        stream
+            .set_write_timeout(Some(SOCKET_TIMEOUT))
+            .map_err(error)?;
+        let mut line = serde_json::to_string(request).map_err(|err| err.to_string())?;
+        line.push('\n');
+        stream.write_all(line.as_bytes()).map_err(error)?;
+
+        let mut reply = String::new();
+        BufReader::new(stream)
+            .read_line(&mut reply)
+            .map_err(error)?;
+        let response: Response = serde_json::from_str(&reply)
+            .map_err(|err| format!("Invalid reply from the agent: {}", err))?;
+        match response.error {
+            Some(err) => Err(err),
+            None => Ok(response),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn request(&self, _request: &Request) -> Result<Response, String> {
+        Err("The agent is only available on Unix.".into())
+    }
+}
+
+/// Starts a background `ttt agent --serve` for `data_file` and hands it the key over a pipe.
+#[cfg(unix)]
+pub fn spawn_agent(data_file: &Path, key: &DataKey, timeout: Duration) -> Result<(), String> {
+    use std::io::Write;
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let exe = std::env::current_exe().map_err(|err| err.to_string())?;
+    let mut child = Command::new(exe)
+        .arg("--data-file")
+        .arg(data_file)
+        .arg("agent")
+        .arg("--serve")
+        .arg("--timeout")
+        .arg(format!("{}s", timeout.as_secs()))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Its own process group, so Ctrl-C in the starting shell does not reach the agent.
+        .process_group(0)
+        .spawn()
+        .map_err(|err| format!("Could not start the agent: {}", err))?;
+    let parts = zeroize::Zeroizing::new(
+        serde_json::to_vec(&key.to_parts()).map_err(|err| err.to_string())?,
+    );
+    let mut stdin = child.stdin.take().ok_or("Could not start the agent.")?;
+    stdin
+        .write_all(&parts)
+        .map_err(|err| format!("Could not start the agent: {}", err))?;
+    drop(stdin);
+
+    for _ in 0..50 {
+        if let Some(client) = AgentClient::find(data_file)
+            && client.status().is_ok()
+        {
+            return Ok(());
+        }
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(format!("The agent exited early ({}).", status));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Err("The agent did not start in time.".into())
+}
+
+#[cfg(not(unix))]
+pub fn spawn_agent(_data_file: &Path, _key: &DataKey, _timeout: Duration) -> Result<(), String> {
+    Err("The agent is only available on Unix.".into())
+}
+
+/// Runs the agent in this process with the key read from stdin, until it times out or is
+/// locked. The key is kept in memory locked against swapping and zeroed when dropped.
+#[cfg(unix)]
+pub fn serve_from_stdin(data_file: &Path, timeout: Duration) -> Result<(), String> {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    let mut input = zeroize::Zeroizing::new(Vec::new());
+    std::io::stdin()
+        .read_to_end(&mut input)
+        .map_err(|err| err.to_string())?;
+    let parts = serde_json::from_slice(&input).map_err(|err| err.to_string())?;
+    let key = Box::new(DataKey::from_parts(&parts)?);
+    drop(input);
+    lock_in_memory(key.bytes());
+
+    let socket = agent_socket_path(data_file);
+    if let Some(client) = AgentClient::find(data_file) {
+        if client.status().is_ok() {
+            return Err(format!(
+                "An agent is already running for {}.",
+                data_file.display()
+            ));
+        }
+        let _ = std::fs::remove_file(&socket);
+    }
+    // The socket is created owner-only, so no other user can connect before the chmod below.
+    // SAFETY: umask only swaps the process file mode mask; it is restored right after bind.
+    let umask = unsafe { libc::umask(0o077) };
+    let bound = UnixListener::bind(&socket);
+    unsafe { libc::umask(umask) };
+    let listener =
+        bound.map_err(|err| format!("Could not listen on {}: {}", socket.display(), err))?;
+    std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))
+        .map_err(|err| err.to_string())?;
+
+    let key = Arc::new(Mutex::new(Some(key)));
+    let shutdown = {
+        let key = Arc::clone(&key);
+        let socket = socket.clone();
+        move || {
+            key.lock().unwrap_or_else(|err| err.into_inner()).take();
+            let _ = std::fs::remove_file(&socket);
+            std::process::exit(0);
+        }
+    };
+    let deadline = Instant::now() + timeout;
+    std::thread::spawn({
+        let shutdown = shutdown.clone();
+        move || {
+            std::thread::sleep(timeout);
+            shutdown();
+        }
+    });
+
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        if !peer_is_owner(&stream) {
+            continue;
+        }
+        let _ = stream.set_read_timeout(Some(SOCKET_TIMEOUT));
+        let mut line = zeroize::Zeroizing::new(String::new());
+        let Ok(reader) = stream.try_clone() else {
+            continue;
+        };
+        if BufReader::new(reader).read_line(&mut line).is_err() {
+            continue;
+        }
+        let guard = key.lock().unwrap_or_else(|err| err.into_inner());
+        let Some(key) = guard.as_ref() else {
+            break;
+        };
+        let locks_in = deadline.saturating_duration_since(Instant::now());
+        let (response, lock) = respond(key, &line, locks_in);
+        drop(guard);
+        if let Ok(mut reply) = serde_json::to_string(&response) {
+            reply.push('\n');
+            let _ = stream.write_all(reply.as_bytes());
+        }
+        if lock {
+            shutdown();
+        }
+    }
+    Ok(())
+}
+
+/// Answers one request line; the flag is set when the agent should lock and exit.
+fn respond(key: &DataKey, line: &str, locks_in: Duration) -> (Response, bool) {
+    let result = match serde_json::from_str(line) {
+        Err(err) => Err(format!("Invalid request: {}", err)),
+        Ok(Request::Status) => Ok(Response {
+            locks_in: Some(locks_in.as_secs()),
+            ..Response::default()
+        }),
+        Ok(Request::Lock) => return (Response::default(), true),
+        Ok(Request::Decrypt { payload }) => key.open(&payload).map(|store| Response {
+            store: Some(store),
+            ..Response::default()
+        }),
+        Ok(Request::Encrypt { store }) => serde_json::to_vec(&store)
+            .map_err(|err| err.to_string())
+            .and_then(|plaintext| key.seal(&zeroize::Zeroizing::new(plaintext)))
+            .map(|sealed| Response {
+                sealed: Some(sealed),
+                ..Response::default()
+            }),
+    };
+    let response = result.unwrap_or_else(|err| Response {
+        error: Some(err),
+        ..Response::default()
+    });
+    (response, false)
+}
+
+#[cfg(not(unix))]
+pub fn serve_from_stdin(_data_file: &Path, _timeout: Duration) -> Result<(), String> {
+    Err("The agent is only available on Unix.".into())
+}
+
+#[cfg(unix)]
+fn lock_in_memory(bytes: &[u8]) {
+    // SAFETY: the pointer and length describe a live allocation; mlock only pins its pages.
+    let locked = unsafe { libc::mlock(bytes.as_ptr().cast(), bytes.len()) } == 0;
+    if !locked {
+        eprintln!("Warning: could not lock the key in memory; it may be swapped to disk.");
+    }
+}
+
+/// Whether the process on the other end of `stream` runs as the same user as the agent.
+#[cfg(unix)]
+fn peer_is_owner(stream: &std::os::unix::net::UnixStream) -> bool {
+    use std::os::fd::AsRawFd;
+
+    let fd = stream.as_raw_fd();
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let peer_uid = {
+        let mut cred = libc::ucred {
+            pid: 0,
+            uid: 0,
+            gid: 0,
+        };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        // SAFETY: `cred` and `len` describe a writable ucred, as SO_PEERCRED expects.
+        let status = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                (&mut cred as *mut libc::ucred).cast(),
+                &mut len,
+            )
+        };
+        (status == 0).then_some(cred.uid)
+    };
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let peer_uid = {
+        let (mut uid, mut gid) = (0, 0);
+        // SAFETY: getpeereid only writes the two ids.
+        let status = unsafe { libc::getpeereid(fd, &mut uid, &mut gid) };
+        (status == 0).then_some(uid)
+    };
+    // SAFETY: getuid has no preconditions.
+    peer_uid == Some(unsafe { libc::getuid() })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn agent_requests_roundtrip_through_the_cached_key() {
+        let key = DataKey::generate();
+        let other = DataKey::generate();
+        let hour = Duration::from_secs(3600);
+        let ask = |key: &DataKey, request: &Request| {
+            let (response, lock) = respond(key, &serde_json::to_string(request).unwrap(), hour);
+            assert!(!lock);
+            response
+        };
+
+        let store = json!({ "version": 4, "tasks": [] });
+        let sealed = ask(
+            &key,
+            &Request::Encrypt {
+                store: store.clone(),
+            },
+        );
+        let sealed = serde_json::to_value(sealed.sealed.unwrap()).unwrap();
+        let mut envelope = json!({ "version": 2, "cipher": "xchacha20poly1305", "slots": [{
+            "kind": "keyfile", "nonce": "", "wrapped_key": "", "created_at": "2026-01-01T00:00:00Z"
+        }] });
+        envelope
+            .as_object_mut()
+            .unwrap()
+            .extend(sealed.as_object().unwrap().clone());
+        let payload = envelope.to_string();
+        let opened = ask(
+            &key,
+            &Request::Decrypt {
+                payload: payload.clone(),
+            },
+        );
+        assert_eq!(opened.store, Some(store));
+
+        // Another data file's key reports an error instead of returning garbage.
+        let refused = ask(&other, &Request::Decrypt { payload });
+        assert!(refused.error.unwrap().contains("does not open"));
+
+        assert_eq!(ask(&key, &Request::Status).locks_in, Some(3600));
+        assert!(respond(&key, r#"{"op":"lock"}"#, hour).1);
+        assert!(respond(&key, "not json", hour).0.error.is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn peers_of_the_same_user_are_served() {
+        let (ours, _theirs) = std::os::unix::net::UnixStream::pair().unwrap();
+        assert!(peer_is_owner(&ours));
+    }
+}
@@ -1,12 +1,17 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+
+use crate::import::ImportSource;
+use crate::list::ListGroup;
+use crate::output::OutputFormat;
+use crate::report::ReportGroup;
 
 #[derive(Parser)]
 #[command(
     name = "ttt",
     about = "Track task time from the command line",
-    after_help = "Examples:\n  ttt start \"Write docs\"\n  ttt pause\n  ttt resume\n  ttt status\n  ttt report\n  ttt stop\n  ttt location\n  ttt edit\n  ttt rekey\n  ttt restore\n  ttt version"
+    after_help = "Examples:\n  ttt start \"Write docs\"\n  ttt start \"Review\" +acme +code-review\n  ttt project add Website --client Acme --rate 95\n  ttt start \"Landing page\" --project Website\n  ttt continue \"Write docs\"\n  ttt pause\n  ttt resume --ago 10m\n  ttt status\n  ttt --format json status\n  ttt report\n  ttt report --week\n  ttt report --by tag\n  ttt report --month --by client\n  ttt report --week --format csv\n  ttt export --from 2026-01-01 > timesheet.csv\n  ttt import --from toggl toggl_export.csv\n  ttt export --format timewarrior > 2026-01.data\n  ttt report --from 2026-01-01 --to 2026-01-31\n  ttt stop --at \"yesterday 17:30\"\n  ttt location\n  ttt edit\n  ttt archive --index 3\n  ttt delete --id <TASK_ID>\n  ttt merge --by-name\n  ttt doctor\n  ttt edit --index 1 --segment-split \"2,10:30\"\n  ttt agent --timeout 8h\n  ttt agent status\n  ttt rekey\n  ttt recovery use\n  ttt decrypt\n  ttt keys add-keyfile ~/.ttt.key\n  ttt --keyfile ~/.ttt.key status\n  ttt restore\n  ttt restore --task <TASK_ID>\n  TTT_PASSPHRASE_FILE=~/.ttt-pass ttt status\n  ttt version"
 )]
 pub struct Cli {
     #[arg(
@@ -15,6 +20,35 @@ pub struct Cli {
         help = "Override the default data file location"
     )]
     pub data_file: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value = "text",
+        value_name = "FORMAT",
+        help = "Output format for list, report, status and export"
+    )]
+    pub format: OutputFormat,
+    #[arg(
+        long,
+        global = true,
+        help = "Save even if the data fails consistency checks, or overwrite existing export files"
+    )]
+    pub force: bool,
+    #[arg(
+        long = "passphrase-fd",
+        global = true,
+        value_name = "FD",
+        help = "Read the passphrase from this file descriptor instead of prompting"
+    )]
+    pub passphrase_fd: Option<u32>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Unlock the data file with this keyfile instead of the passphrase"
+    )]
+    pub keyfile: Option<PathBuf>,
     #[command(subcommand)]
     pub command: Command,
 }
@@ -23,15 +57,47 @@ pub struct Cli {
 pub enum Command {
     #[command(about = "Start tracking a task")]
     Start {
-        #[arg(value_name = "TASK", help = "Task name to track (prompted if omitted)")]
+        #[arg(
+            value_name = "TASK",
+            help = "Task name to track, with optional +tags (prompted if omitted)"
+        )]
+        task: Vec<String>,
+        #[arg(long, value_name = "PROJECT", help = "Link the task to a project")]
+        project: Option<String>,
+        #[arg(
+            long = "continue",
+            conflicts_with = "project",
+            help = "Continue a stopped task with this name (or the latest one) instead of creating a new task"
+        )]
+        continue_task: bool,
+        #[command(flatten)]
+        when: EventTimeArgs,
+    },
+    #[command(about = "Continue a stopped task, adding a new segment to it")]
+    Continue {
+        #[arg(
+            value_name = "TASK",
+            help = "Task id, index or name (default: the most recently stopped task)"
+        )]
         task: Option<String>,
+        #[command(flatten)]
+        when: EventTimeArgs,
     },
     #[command(about = "Stop the active or paused task")]
-    Stop,
+    Stop {
+        #[command(flatten)]
+        when: EventTimeArgs,
+    },
     #[command(about = "Pause the active task")]
-    Pause,
+    Pause {
+        #[command(flatten)]
+        when: EventTimeArgs,
+    },
     #[command(about = "Resume the paused task")]
-    Resume,
+    Resume {
+        #[command(flatten)]
+        when: EventTimeArgs,
+    },
     #[command(about = "Show the current task and elapsed time")]
     Status,
     #[command(about = "Show the data file location")]
@@ -42,11 +108,77 @@ pub enum Command {
         today: bool,
         #[arg(long, help = "Only tasks with activity this week")]
         week: bool,
+        #[arg(
+            long = "tag",
+            value_name = "TAG",
+            help = "Only tasks carrying this tag (repeatable)"
+        )]
+        tags: Vec<String>,
+        #[arg(
+            long = "by",
+            value_enum,
+            value_name = "GROUP",
+            help = "Group tasks with subtotals by project or client"
+        )]
+        group: Option<ListGroup>,
+        #[arg(long, help = "Include archived tasks")]
+        archived: bool,
     },
-    #[command(about = "Show today's totals (default)")]
+    #[command(about = "Show totals for today or a date range")]
     Report {
         #[arg(long, help = "Report today's totals (default)")]
         today: bool,
+        #[arg(long, help = "Report yesterday's totals")]
+        yesterday: bool,
+        #[arg(long, help = "Report this week's totals per day")]
+        week: bool,
+        #[arg(long, help = "Report this month's totals per day")]
+        month: bool,
+        #[arg(
+            long,
+            value_name = "YYYY-MM-DD",
+            help = "First day of a custom range (inclusive)"
+        )]
+        from: Option<String>,
+        #[arg(
+            long,
+            value_name = "YYYY-MM-DD",
+            help = "Last day of a custom range (inclusive, default today)"
+        )]
+        to: Option<String>,
+        #[arg(
+            long = "by",
+            value_enum,
+            value_name = "GROUP",
+            default_value = "task",
+            help = "Group entries by task, tag, project or client"
+        )]
+        group: ReportGroup,
+        #[command(flatten)]
+        csv: CsvArgs,
+    },
+    #[command(about = "Export time segments (CSV unless --format json/ndjson)")]
+    Export {
+        #[arg(
+            long,
+            value_name = "YYYY-MM-DD",
+            help = "Only segments from this day on (inclusive)"
+        )]
+        from: Option<String>,
+        #[arg(
+            long,
+            value_name = "YYYY-MM-DD",
+            help = "Only segments up to this day (inclusive)"
+        )]
+        to: Option<String>,
+        #[arg(
+            long,
+            value_name = "DIR",
+            help = "Watson data directory to write frames and state into"
+        )]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        csv: CsvArgs,
     },
     #[command(about = "Edit a task name or time segments")]
     Edit {
@@ -62,27 +194,312 @@ pub enum Command {
         name: Option<String>,
         #[arg(
             long,
-            value_name = "RFC3339|now",
-            help = "Override created time (RFC3339 or 'now')"
+            value_name = "PROJECT|none",
+            help = "Link the task to a project ('none' to unlink)"
+        )]
+        project: Option<String>,
+        #[arg(
+            long,
+            value_name = "TIME",
+            allow_hyphen_values = true,
+            help = "Override created time (e.g. 'now', '09:15', 'yesterday 17:00', RFC3339)"
         )]
         created_at: Option<String>,
         #[arg(
             long,
-            value_name = "RFC3339|open",
-            help = "Override closed time (RFC3339 or 'open')"
+            value_name = "TIME|open",
+            allow_hyphen_values = true,
+            help = "Override closed time (a time or 'open')"
         )]
         closed_at: Option<String>,
         #[arg(
             long,
             value_name = "INDEX,START,END",
+            allow_hyphen_values = true,
             help = "Edit a segment (1-based). END can be 'open'."
         )]
         segment_edit: Vec<String>,
+        #[arg(
+            long,
+            value_name = "START,END",
+            allow_hyphen_values = true,
+            help = "Insert a closed segment (repeatable)"
+        )]
+        segment_add: Vec<String>,
+        #[arg(
+            long,
+            value_name = "N",
+            group = "segment_change",
+            help = "Delete segment N"
+        )]
+        segment_delete: Option<usize>,
+        #[arg(
+            long,
+            value_name = "N,AT",
+            allow_hyphen_values = true,
+            group = "segment_change",
+            help = "Split segment N in two at the given time"
+        )]
+        segment_split: Option<String>,
+        #[arg(
+            long,
+            value_name = "N,M",
+            group = "segment_change",
+            help = "Merge adjacent segments N and M"
+        )]
+        segment_merge: Option<String>,
+    },
+    #[command(about = "Import time entries from another tracker's export")]
+    Import {
+        #[arg(
+            long = "from",
+            value_enum,
+            value_name = "SOURCE",
+            help = "Tool that produced the export"
+        )]
+        source: ImportSource,
+        #[arg(
+            value_name = "FILE",
+            help = "CSV export, Timewarrior .data file or Watson frames file"
+        )]
+        file: PathBuf,
+        #[arg(long, help = "Show what would be imported without saving")]
+        dry_run: bool,
+    },
+    #[command(about = "Delete a task and all of its time")]
+    Delete {
+        #[arg(long, value_name = "ID", help = "Task id to delete")]
+        id: Option<String>,
+        #[arg(
+            long,
+            value_name = "INDEX",
+            help = "Task index from the selection list (1-based)"
+        )]
+        index: Option<usize>,
+    },
+    #[command(about = "Hide a stopped task from list and edit (reports still count it)")]
+    Archive {
+        #[arg(long, value_name = "ID", help = "Task id to archive")]
+        id: Option<String>,
+        #[arg(
+            long,
+            value_name = "INDEX",
+            help = "Task index from the selection list (1-based)"
+        )]
+        index: Option<usize>,
+    },
+    #[command(about = "Bring an archived task back")]
+    Unarchive {
+        #[arg(long, value_name = "ID", help = "Task id to unarchive")]
+        id: Option<String>,
+        #[arg(
+            long,
+            value_name = "INDEX",
+            help = "Archived task index from the selection list (1-based)"
+        )]
+        index: Option<usize>,
+    },
+    #[command(about = "Merge duplicate tasks into one")]
+    Merge {
+        #[arg(
+            value_name = "ID",
+            required_unless_present = "by_name",
+            conflicts_with = "by_name",
+            help = "Ids of the tasks to merge"
+        )]
+        ids: Vec<String>,
+        #[arg(
+            long,
+            value_name = "ID",
+            required_unless_present = "by_name",
+            help = "Task that receives the segments"
+        )]
+        into: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "into",
+            help = "Merge every group of tasks sharing a name (case and spacing ignored)"
+        )]
+        by_name: bool,
+    },
+    #[command(about = "Find and repair inconsistent time data")]
+    Doctor {
+        #[arg(long, help = "Apply every proposed fix without asking")]
+        apply: bool,
+    },
+    #[command(about = "Manage projects")]
+    Project {
+        #[command(subcommand)]
+        action: ProjectCommand,
+    },
+    #[command(about = "Manage clients")]
+    Client {
+        #[command(subcommand)]
+        action: ClientCommand,
     },
     #[command(about = "Change the passphrase for the data file")]
     Rekey,
-    #[command(about = "Restore the data file from a backup")]
-    Restore,
+    #[command(about = "Encrypt a plain data file in place")]
+    Encrypt,
+    #[command(about = "Store the data file as plain JSON (not encrypted)")]
+    Decrypt,
+    #[command(about = "Create recovery codes, or reset a forgotten passphrase with one")]
+    Recovery {
+        #[command(subcommand)]
+        action: RecoveryCommand,
+    },
+    #[command(about = "List or change the ways the data file can be unlocked")]
+    Keys {
+        #[command(subcommand)]
+        action: Option<KeysCommand>,
+    },
+    #[command(about = "Keep the unlocked key in a background agent")]
+    Agent {
+        #[command(subcommand)]
+        action: Option<AgentCommand>,
+        #[arg(
+            long,
+            value_name = "DURATION",
+            default_value = "1h",
+            help = "Forget the key after this long"
+        )]
+        timeout: String,
+        #[arg(long, hide = true)]
+        serve: bool,
+    },
+    #[command(about = "Restore the data file, or selected tasks, from a backup")]
+    Restore {
+        #[arg(
+            long = "task",
+            value_name = "ID",
+            help = "Restore only this task from the backup (repeatable)"
+        )]
+        tasks: Vec<String>,
+    },
     #[command(about = "Show the CLI version")]
     Version,
 }
+
+#[derive(Args)]
+pub struct EventTimeArgs {
+    #[arg(
+        long,
+        value_name = "TIME",
+        conflicts_with = "ago",
+        allow_hyphen_values = true,
+        help = "When it happened (e.g. 14:30, '-20m', 'yesterday 17:00', RFC3339)"
+    )]
+    pub at: Option<String>,
+    #[arg(
+        long,
+        value_name = "DURATION",
+        help = "How long ago it happened (e.g. 20m, 1h30m)"
+    )]
+    pub ago: Option<String>,
+}
+
+#[derive(Args)]
+pub struct CsvArgs {
+    #[arg(
+        long,
+        value_name = "CHAR",
+        default_value = ",",
+        help = "CSV field delimiter (a single character or 'tab')"
+    )]
+    pub delimiter: String,
+    #[arg(long, help = "Omit the CSV header row")]
+    pub no_header: bool,
+}
+
+#[derive(Subcommand)]
+pub enum KeysCommand {
+    #[command(about = "List the unlock methods (default)")]
+    List,
+    #[command(about = "Let a keyfile unlock the data file, creating the keyfile if it is missing")]
+    AddKeyfile {
+        #[arg(value_name = "PATH", help = "Keyfile path")]
+        path: PathBuf,
+    },
+    #[command(about = "Remove an unlock method")]
+    Remove {
+        #[arg(value_name = "N", help = "Number from \"ttt keys\"")]
+        number: usize,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RecoveryCommand {
+    #[command(about = "Replace all recovery codes with a new set")]
+    Generate {
+        #[arg(long, value_name = "N", default_value_t = 8, help = "Number of codes")]
+        count: usize,
+    },
+    #[command(about = "Unlock with a recovery code and choose a new passphrase")]
+    Use,
+}
+
+#[derive(Subcommand)]
+pub enum ProjectCommand {
+    #[command(about = "Create a project")]
+    Add {
+        #[arg(value_name = "NAME", help = "Project name")]
+        name: String,
+        #[arg(long, value_name = "CLIENT", help = "Client the project belongs to")]
+        client: Option<String>,
+        #[arg(
+            long,
+            value_name = "RATE",
+            help = "Hourly rate (overrides the client rate)"
+        )]
+        rate: Option<f64>,
+    },
+    #[command(about = "List projects")]
+    List {
+        #[arg(long, help = "Include archived projects")]
+        archived: bool,
+    },
+    #[command(about = "Archive a project so new tasks cannot use it")]
+    Archive {
+        #[arg(value_name = "NAME", help = "Project name or id")]
+        name: String,
+    },
+    #[command(about = "Reactivate an archived project")]
+    Unarchive {
+        #[arg(value_name = "NAME", help = "Project name or id")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AgentCommand {
+    #[command(about = "Make the agent forget the key and exit")]
+    Lock,
+    #[command(about = "Show whether an agent is running")]
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum ClientCommand {
+    #[command(about = "Create a client")]
+    Add {
+        #[arg(value_name = "NAME", help = "Client name")]
+        name: String,
+        #[arg(long, value_name = "RATE", help = "Default hourly rate")]
+        rate: Option<f64>,
+    },
+    #[command(about = "List clients")]
+    List {
+        #[arg(long, help = "Include archived clients")]
+        archived: bool,
+    },
+    #[command(about = "Archive a client")]
+    Archive {
+        #[arg(value_name = "NAME", help = "Client name or id")]
+        name: String,
+    },
+    #[command(about = "Reactivate an archived client")]
+    Unarchive {
+        #[arg(value_name = "NAME", help = "Client name or id")]
+        name: String,
+    },
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+/// Optional settings read from `config.json` in the user config directory or `$TTT_CONFIG`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub backup: BackupConfig,
+    /// Shell command whose first output line is the passphrase, e.g. `pass show ttt`.
+    pub passphrase_command: Option<String>,
+    /// Keyfile that unlocks the data file instead of the passphrase.
+    pub keyfile: Option<PathBuf>,
+    /// Whether new data files are encrypted; `ttt encrypt`/`decrypt` convert existing ones.
+    pub encryption: Encryption,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encryption {
+    /// Plain JSON, for data kept on an already encrypted disk.
+    None,
+    #[default]
+    Passphrase,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    /// The newest backups that are always kept.
+    pub keep_last: usize,
+    /// One backup per hour is kept for this many hours.
+    pub hourly_hours: i64,
+    /// One backup per day is kept for this many days.
+    pub daily_days: i64,
+    /// Directory for backups; defaults to the data file's directory.
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig {
+            keep_last: 10,
+            hourly_hours: 24,
+            daily_days: 30,
+            dir: None,
+        }
+    }
+}
+
+pub fn config_file_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("TTT_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    ProjectDirs::from("com", "ttt", "ttt").map(|dirs| dirs.config_dir().join("config.json"))
+}
+
+pub fn load_config() -> Result<Config, String> {
+    let Some(path) = config_file_path() else {
+        return Ok(Config::default());
+    };
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let contents = fs::read_to_string(&path)
+        .map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+    serde_json::from_str(&contents)
+        .map_err(|err| format!("Invalid config file {}: {}", path.display(), err))
+}
@@ -1,28 +1,51 @@
+use std::cell::{Cell, OnceCell, RefCell};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
 use argon2::{Algorithm, Argon2, Params, Version};
 use base64::{Engine as _, engine::general_purpose};
 use chacha20poly1305::aead::{Aead, KeyInit};
 use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use chrono::{DateTime, Utc};
 use rand_core::{OsRng, RngCore};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
 
+use crate::agent::AgentClient;
+use crate::config::Encryption;
 use crate::model::Store;
 
-const ENVELOPE_VERSION: u32 = 1;
+/// Version 1 envelopes encrypt with the passphrase-derived key; version 2 adds key slots.
+const ENVELOPE_VERSION: u32 = 2;
 const KDF_NAME: &str = "argon2id";
 const CIPHER_NAME: &str = "xchacha20poly1305";
 const SALT_LEN: usize = 16;
+/// Recovery codes avoid look-alike characters (no I, O, 0 or 1).
+const RECOVERY_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+/// 16 characters of 5 bits each, printed in groups of four.
+const RECOVERY_CODE_LEN: usize = 16;
+pub const RECOVERY_CODE_COUNT: usize = 8;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct EncryptedStore {
     version: u32,
-    kdf: KdfConfig,
     cipher: String,
-    salt: String,
-    nonce: String,
-    ciphertext: String,
+    /// Version 1 only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<KdfConfig>,
+    /// Version 1 only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    slots: Vec<KeySlot>,
+    #[serde(flatten)]
+    sealed: SealedData,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 struct KdfConfig {
     name: String,
     m_cost: u32,
@@ -30,20 +53,56 @@ struct KdfConfig {
     p_cost: u32,
 }
 
-pub fn read_passphrase(confirm: bool) -> Result<String, String> {
+/// Non-interactive places to read the passphrase from, tried in this order before prompting.
+#[derive(Default)]
+pub struct PassphraseSources {
+    /// `TTT_PASSPHRASE_FILE`
+    pub file: Option<PathBuf>,
+    /// `--passphrase-fd`
+    pub fd: Option<u32>,
+    /// `passphrase_command` from the config file
+    pub command: Option<String>,
+}
+
+/// Reads the passphrase from the first configured source, or prompts on the terminal. A
+/// `confirm` prompt is only shown when prompting, since the other sources are not typed.
+pub fn read_passphrase(sources: &PassphraseSources, confirm: bool) -> Result<String, String> {
+    let (passphrase, source) = if let Some(path) = &sources.file {
+        let contents = fs::read_to_string(path).map_err(|err| {
+            format!(
+                "Could not read TTT_PASSPHRASE_FILE {}: {}",
+                path.display(),
+                err
+            )
+        })?;
+        (first_line(&contents), "TTT_PASSPHRASE_FILE")
+    } else if let Some(fd) = sources.fd {
+        (read_passphrase_fd(fd)?, "--passphrase-fd")
+    } else if let Some(command) = &sources.command {
+        (run_passphrase_command(command)?, "passphrase_command")
+    } else {
+        return prompt_passphrase(confirm);
+    };
+    if passphrase.trim().is_empty() {
+        return Err(format!("The passphrase from {} is empty.", source));
+    }
+    Ok(passphrase)
+}
+
+pub fn prompt_passphrase(confirm: bool) -> Result<String, String> {
     let prompt = if confirm {
         "New passphrase: "
     } else {
         "Passphrase: "
     };
-    let passphrase = rpassword::prompt_password(prompt).map_err(|err| err.to_string())?;
+    let passphrase = rpassword::prompt_password(prompt).map_err(no_terminal)?;
     if passphrase.trim().is_empty() {
         return Err("Passphrase cannot be empty.".into());
     }
 
     if confirm {
         let confirm_passphrase =
-            rpassword::prompt_password("Confirm passphrase: ").map_err(|err| err.to_string())?;
+            rpassword::prompt_password("Confirm passphrase: ").map_err(no_terminal)?;
         if passphrase != confirm_passphrase {
             return Err("Passphrases do not match.".into());
         }
@@ -52,75 +111,741 @@ pub fn read_passphrase(confirm: bool) -> Result<String, String> {
     Ok(passphrase)
 }
 
-pub fn encrypt_store(store: &Store, passphrase: &str) -> Result<String, String> {
-    if passphrase.trim().is_empty() {
-        return Err("Passphrase cannot be empty.".into());
+/// Recovery codes are read like passphrases, without echo, and only from the terminal.
+pub fn prompt_recovery_code() -> Result<String, String> {
+    rpassword::prompt_password("Recovery code: ")
+        .map_err(|err| format!("Could not prompt for the recovery code: {}", err))
+}
+
+fn no_terminal(err: io::Error) -> String {
+    format!(
+        "Could not prompt for the passphrase ({}). Without a terminal, set TTT_PASSPHRASE_FILE, pass --passphrase-fd or configure passphrase_command.",
+        err
+    )
+}
+
+#[cfg(unix)]
+fn read_passphrase_fd(fd: u32) -> Result<String, String> {
+    fs::read_to_string(format!("/dev/fd/{}", fd))
+        .map(|contents| first_line(&contents))
+        .map_err(|err| format!("Could not read the passphrase from fd {}: {}", fd, err))
+}
+
+#[cfg(not(unix))]
+fn read_passphrase_fd(_fd: u32) -> Result<String, String> {
+    Err("--passphrase-fd is only supported on Unix.".into())
+}
+
+fn run_passphrase_command(command: &str) -> Result<String, String> {
+    #[cfg(unix)]
+    let mut process = Command::new("sh");
+    #[cfg(unix)]
+    process.arg("-c");
+    #[cfg(not(unix))]
+    let mut process = Command::new("cmd");
+    #[cfg(not(unix))]
+    process.arg("/C");
+    let output = process
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| format!("Could not run passphrase_command \"{}\": {}", command, err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "passphrase_command \"{}\" failed ({}).",
+            command, output.status
+        ));
     }
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|_| "passphrase_command printed invalid UTF-8.".to_string())?;
+    Ok(first_line(&stdout))
+}
 
-    let payload = serde_json::to_vec(store).map_err(|err| err.to_string())?;
-    let kdf = default_kdf();
-    let salt = random_bytes(SALT_LEN);
-    let key = derive_key(passphrase, &salt, &kdf)?;
+/// Passphrase files and commands like `pass show` put the secret on the first line.
+fn first_line(contents: &str) -> String {
+    contents
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('\r')
+        .to_string()
+}
 
-    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
-    let nonce_bytes = random_bytes(24);
-    let nonce = XNonce::from_slice(&nonce_bytes);
-    let ciphertext = cipher
-        .encrypt(nonce, payload.as_ref())
-        .map_err(|_| "Encryption failed.".to_string())?;
+/// The random key that encrypts the store. Key slots in the envelope each wrap it for one unlock
+/// method, so changing a passphrase or adding a keyfile rewrites only the header. Files from
+/// before key slots encrypt with the passphrase-derived key directly; that key becomes their
+/// data key. The bytes are zeroed on drop.
+pub struct DataKey {
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl DataKey {
+    pub fn generate() -> Self {
+        let mut key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(key.as_mut_slice());
+        DataKey { key }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        self.key.as_slice()
+    }
+
+    pub fn to_parts(&self) -> KeyParts {
+        KeyParts {
+            key: general_purpose::STANDARD.encode(self.key.as_slice()),
+        }
+    }
+
+    pub fn from_parts(parts: &KeyParts) -> Result<Self, String> {
+        let bytes = Zeroizing::new(decode(&parts.key, "key")?);
+        Ok(DataKey {
+            key: key_from_bytes(&bytes).ok_or("Invalid key length.")?,
+        })
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> Result<SealedData, String> {
+        let (nonce, ciphertext) = encrypt(&self.key, plaintext)?;
+        Ok(SealedData {
+            nonce: general_purpose::STANDARD.encode(nonce),
+            ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        })
+    }
+
+    /// Returns the decrypted store as raw JSON so that older versions can be migrated first.
+    pub fn open(&self, payload: &str) -> Result<Value, String> {
+        self.open_sealed(&parse_envelope(payload)?.sealed)
+    }
+
+    fn open_sealed(&self, sealed: &SealedData) -> Result<Value, String> {
+        let nonce = decode(&sealed.nonce, "nonce")?;
+        let ciphertext = decode(&sealed.ciphertext, "ciphertext")?;
+        let plaintext = Zeroizing::new(
+            decrypt(&self.key, &nonce, &ciphertext)
+                .ok_or("The key does not open this data file, or the file is corrupted.")?,
+        );
+        serde_json::from_slice(&plaintext).map_err(|err| err.to_string())
+    }
+
+    fn wrap(
+        &self,
+        kind: SlotKind,
+        kek: &[u8; 32],
+        kdf: Option<(KdfConfig, Vec<u8>)>,
+    ) -> Result<KeySlot, String> {
+        let (nonce, wrapped) = encrypt(kek, self.key.as_slice())?;
+        let (kdf, salt) = kdf.unzip();
+        Ok(KeySlot {
+            kind,
+            kdf,
+            salt: salt.map(|salt| general_purpose::STANDARD.encode(salt)),
+            nonce: general_purpose::STANDARD.encode(nonce),
+            wrapped_key: general_purpose::STANDARD.encode(wrapped),
+            created_at: Utc::now(),
+        })
+    }
+
+    /// A passphrase slot with a fresh salt.
+    pub fn wrap_passphrase(&self, passphrase: &str) -> Result<KeySlot, String> {
+        check_passphrase(passphrase)?;
+        self.wrap_secret(SlotKind::Passphrase, passphrase)
+    }
+
+    fn wrap_secret(&self, kind: SlotKind, secret: &str) -> Result<KeySlot, String> {
+        let kdf = default_kdf();
+        let salt = random_bytes(SALT_LEN);
+        let kek = derive_key(secret, &salt, &kdf)?;
+        self.wrap(kind, &kek, Some((kdf, salt)))
+    }
+
+    /// New one-time recovery codes and a slot for each; the codes are not stored anywhere.
+    pub fn recovery_slots(&self, count: usize) -> Result<(Vec<KeySlot>, Vec<String>), String> {
+        let mut slots = Vec::new();
+        let mut codes = Vec::new();
+        for _ in 0..count {
+            let code = generate_recovery_code();
+            slots.push(self.wrap_secret(SlotKind::Recovery, &normalize_recovery_code(&code))?);
+            codes.push(code);
+        }
+        Ok((slots, codes))
+    }
+
+    pub fn wrap_keyfile(&self, keyfile: &Keyfile) -> Result<KeySlot, String> {
+        self.wrap(SlotKind::Keyfile, &keyfile.0, None)
+    }
+}
+
+/// The encrypted store inside an envelope, produced by the data key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SealedData {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// A data key in transferable form, handed to the agent process over a pipe.
+#[derive(Serialize, Deserialize)]
+pub struct KeyParts {
+    key: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlotKind {
+    Passphrase,
+    Keyfile,
+    Recovery,
+}
+
+impl SlotKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            SlotKind::Passphrase => "passphrase",
+            SlotKind::Keyfile => "keyfile",
+            SlotKind::Recovery => "recovery",
+        }
+    }
+}
+
+/// One way to unlock a data file: the data key encrypted with a key-encryption key that comes
+/// from a passphrase or recovery code (via the slot's KDF and salt) or directly from a keyfile.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeySlot {
+    pub kind: SlotKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<KdfConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    nonce: String,
+    wrapped_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl KeySlot {
+    pub fn matches_keyfile(&self, keyfile: &Keyfile) -> bool {
+        self.kind == SlotKind::Keyfile && self.unwrap_with(&keyfile.0).is_some()
+    }
+
+    fn unwrap_with(&self, kek: &[u8; 32]) -> Option<DataKey> {
+        let nonce = decode(&self.nonce, "nonce").ok()?;
+        let wrapped = decode(&self.wrapped_key, "key").ok()?;
+        let bytes = Zeroizing::new(decrypt(kek, &nonce, &wrapped)?);
+        key_from_bytes(&bytes).map(|key| DataKey { key })
+    }
+
+    fn unwrap_secret(&self, secret: &str) -> Result<Option<DataKey>, String> {
+        let (Some(kdf), Some(salt)) = (&self.kdf, &self.salt) else {
+            return Err(format!("Invalid {} slot.", self.kind.label()));
+        };
+        check_kdf(kdf)?;
+        let kek = derive_key(secret, &decode(salt, "salt")?, kdf)?;
+        Ok(self.unwrap_with(&kek))
+    }
+}
+
+/// A random 32-byte file that unlocks a keyfile slot without a passphrase.
+pub struct Keyfile(Zeroizing<[u8; 32]>);
+
+impl Keyfile {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let bytes = Zeroizing::new(
+            fs::read(path)
+                .map_err(|err| format!("Could not read keyfile {}: {}", path.display(), err))?,
+        );
+        key_from_bytes(&bytes).map(Keyfile).ok_or_else(|| {
+            format!(
+                "Keyfile {} must contain exactly 32 bytes; create one with \"ttt keys add-keyfile\".",
+                path.display()
+            )
+        })
+    }
+
+    /// Writes a new random keyfile readable only by the owner; an existing file is never replaced.
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let keyfile = Keyfile(DataKey::generate().key);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let error =
+            |err: io::Error| format!("Could not create keyfile {}: {}", path.display(), err);
+        let mut file = options.open(path).map_err(error)?;
+        io::Write::write_all(&mut file, keyfile.0.as_slice()).map_err(error)?;
+        file.sync_all().map_err(error)?;
+        Ok(keyfile)
+    }
+}
+
+/// A plain data file as ttt writes it: the store with an `"encryption": "none"` marker, which
+/// tells a deliberately plain file apart from plain JSON put in place of an encrypted one.
+#[derive(Serialize)]
+struct PlainFile<'a> {
+    encryption: Encryption,
+    #[serde(flatten)]
+    store: &'a Store,
+}
+
+/// Opens and seals data file envelopes for one command. A running agent is tried first;
+/// otherwise the data key is unlocked on first use with the keyfile or the passphrase and
+/// kept, together with the key slots of the last envelope opened, for the next save. Files
+/// that are plain JSON are read and written as such.
+pub struct Unlocker {
+    agent: Option<AgentClient>,
+    configured: Encryption,
+    encryption: Cell<Encryption>,
+    sources: PassphraseSources,
+    keyfile_path: Option<PathBuf>,
+    confirm: bool,
+    passphrase: OnceCell<String>,
+    keyfile: OnceCell<Keyfile>,
+    key: RefCell<Option<DataKey>>,
+    slots: RefCell<Vec<KeySlot>>,
+    recovery_codes: RefCell<Vec<String>>,
+}
+
+impl Unlocker {
+    pub fn new(
+        sources: PassphraseSources,
+        keyfile: Option<PathBuf>,
+        confirm: bool,
+        agent: Option<AgentClient>,
+    ) -> Self {
+        Unlocker {
+            agent,
+            configured: Encryption::Passphrase,
+            encryption: Cell::new(Encryption::Passphrase),
+            sources,
+            keyfile_path: keyfile,
+            confirm,
+            passphrase: OnceCell::new(),
+            keyfile: OnceCell::new(),
+            key: RefCell::new(None),
+            slots: RefCell::new(Vec::new()),
+            recovery_codes: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The mode for a file that does not exist yet; opening a file switches to its mode. Plain
+    /// files without the marker are only opened when this is `None`.
+    pub fn with_encryption(mut self, encryption: Encryption) -> Self {
+        self.configured = encryption;
+        self.encryption.set(encryption);
+        self
+    }
+
+    pub fn encryption(&self) -> Encryption {
+        self.encryption.get()
+    }
+
+    /// Reads the keyfile or the passphrase now unless `data_file` is plain JSON or the agent
+    /// answers, so that prompting happens before the data file is locked.
+    pub fn prepare(&self, data_file: &Path) -> Result<(), String> {
+        let encrypted = match fs::read_to_string(data_file) {
+            Ok(payload) => is_encrypted(&payload),
+            Err(_) => self.encryption() == Encryption::Passphrase,
+        };
+        if !encrypted
+            || self
+                .agent
+                .as_ref()
+                .is_some_and(|agent| agent.status().is_ok())
+        {
+            return Ok(());
+        }
+        if self.keyfile_path.is_some() {
+            return self.keyfile().map(|_| ());
+        }
+        self.passphrase().map(|_| ())
+    }
+
+    pub fn passphrase(&self) -> Result<&str, String> {
+        if let Some(passphrase) = self.passphrase.get() {
+            return Ok(passphrase);
+        }
+        let passphrase = read_passphrase(&self.sources, self.confirm)?;
+        Ok(self.passphrase.get_or_init(|| passphrase))
+    }
+
+    fn keyfile(&self) -> Result<Option<&Keyfile>, String> {
+        let Some(path) = &self.keyfile_path else {
+            return Ok(None);
+        };
+        if let Some(keyfile) = self.keyfile.get() {
+            return Ok(Some(keyfile));
+        }
+        let keyfile = Keyfile::read(path)?;
+        Ok(Some(self.keyfile.get_or_init(|| keyfile)))
+    }
+
+    pub fn open(&self, payload: &str) -> Result<Value, String> {
+        let mut value: Value =
+            serde_json::from_str(payload).map_err(|err| format!("Invalid data file: {}", err))?;
+        if !is_envelope(&value) {
+            let marked = value
+                .as_object_mut()
+                .and_then(|fields| fields.remove("encryption"))
+                .is_some_and(|marker| marker == "none");
+            if !marked && self.configured != Encryption::None {
+                return Err("The data file is plain JSON without the \"encryption\": \"none\" marker that ttt writes, but the config expects encryption. Run \"ttt encrypt\" to encrypt it, or set \"encryption\": \"none\" in the config file to keep it plain.".into());
+            }
+            self.encryption.set(Encryption::None);
+            self.key.replace(None);
+            self.slots.replace(Vec::new());
+            return Ok(value);
+        }
+        self.encryption.set(Encryption::Passphrase);
+        let envelope = serde_json::from_value(value)
+            .map_err(|err| err.to_string())
+            .and_then(check_envelope)?;
+        // Files without slots skip the agent, since their first save needs the key here to
+        // wrap it in a passphrase slot.
+        if !envelope.slots.is_empty()
+            && let Some(agent) = &self.agent
+            && let Ok(store) = agent.decrypt(payload)
+        {
+            // The agent holds the key for these slots; a key cached for another file is dropped.
+            self.key.replace(None);
+            self.slots.replace(envelope.slots);
+            return Ok(store);
+        }
+        if let Some(key) = self.key.borrow().as_ref()
+            && let Ok(store) = key.open_sealed(&envelope.sealed)
+        {
+            self.slots.replace(envelope.slots);
+            return Ok(store);
+        }
+        let key = self.unlock(&envelope)?;
+        let store = key.open_sealed(&envelope.sealed)?;
+        self.key.replace(Some(key));
+        self.slots.replace(envelope.slots);
+        Ok(store)
+    }
+
+    fn unlock(&self, envelope: &EncryptedStore) -> Result<DataKey, String> {
+        if let (Some(kdf), Some(salt)) = (&envelope.kdf, &envelope.salt) {
+            check_kdf(kdf)?;
+            let salt = decode(salt, "salt")?;
+            if salt.is_empty() {
+                return Err("Invalid salt length.".into());
+            }
+            let key = derive_key(self.passphrase()?, &salt, kdf)?;
+            return Ok(DataKey { key });
+        }
+        self.unlock_slots(&envelope.slots)
+    }
+
+    fn unlock_slots(&self, slots: &[KeySlot]) -> Result<DataKey, String> {
+        let of_kind = |kind| slots.iter().filter(move |slot| slot.kind == kind);
+        if let Some(keyfile) = self.keyfile()? {
+            if let Some(key) =
+                of_kind(SlotKind::Keyfile).find_map(|slot| slot.unwrap_with(&keyfile.0))
+            {
+                return Ok(key);
+            }
+            if of_kind(SlotKind::Passphrase).next().is_none() {
+                return Err("The keyfile does not unlock this data file.".into());
+            }
+        }
+        let recovery_hint = if of_kind(SlotKind::Recovery).next().is_some() {
+            " If you forgot the passphrase, run \"ttt recovery use\" with a recovery code."
+        } else {
+            ""
+        };
+        if of_kind(SlotKind::Passphrase).next().is_none() {
+            return Err(format!(
+                "This data file has no passphrase; unlock it with --keyfile or TTT_KEYFILE.{}",
+                recovery_hint
+            ));
+        }
+        let passphrase = self.passphrase()?;
+        for slot in of_kind(SlotKind::Passphrase) {
+            if let Some(key) = slot.unwrap_secret(passphrase)? {
+                return Ok(key);
+            }
+        }
+        Err(format!(
+            "Invalid passphrase or corrupted data file.{}",
+            recovery_hint
+        ))
+    }
+
+    pub fn seal(&self, store: &Store) -> Result<String, String> {
+        if self.encryption() == Encryption::None {
+            let file = PlainFile {
+                encryption: Encryption::None,
+                store,
+            };
+            return serde_json::to_string_pretty(&file).map_err(|err| err.to_string());
+        }
+        if self.key.borrow().is_none()
+            && !self.slots.borrow().is_empty()
+            && let Some(agent) = &self.agent
+        {
+            let value = serde_json::to_value(store).map_err(|err| err.to_string())?;
+            if let Ok(sealed) = agent.encrypt(&value) {
+                return envelope_json(self.slots(), sealed);
+            }
+        }
+        let plaintext = Zeroizing::new(serde_json::to_vec(store).map_err(|err| err.to_string())?);
+        let sealed = self.with_key(|key| key.seal(&plaintext))?;
+        envelope_json(self.slots(), sealed)
+    }
+
+    /// Runs `f` with the data key of the last opened file. A new file gets a new data key and
+    /// recovery codes, and a file without slots its first passphrase slot.
+    pub fn with_key<T>(&self, f: impl FnOnce(&DataKey) -> Result<T, String>) -> Result<T, String> {
+        let mut recovery = Vec::new();
+        if self.key.borrow().is_none() {
+            let key = if self.slots.borrow().is_empty() {
+                let key = DataKey::generate();
+                let (slots, codes) = key.recovery_slots(RECOVERY_CODE_COUNT)?;
+                recovery = slots;
+                self.recovery_codes.replace(codes);
+                key
+            } else {
+                self.unlock_slots(&self.slots.borrow())?
+            };
+            self.key.replace(Some(key));
+        }
+        let key = self.key.borrow();
+        let key = key.as_ref().unwrap();
+        if self.slots.borrow().is_empty() {
+            let mut slots = vec![key.wrap_passphrase(self.passphrase()?)?];
+            slots.append(&mut recovery);
+            self.slots.replace(slots);
+        }
+        f(key)
+    }
+
+    /// Unlocks the data key of `payload` with a recovery code instead of the passphrase.
+    pub fn open_with_recovery_code(&self, payload: &str, code: &str) -> Result<(), String> {
+        let envelope = parse_envelope(payload)?;
+        let key = unlock_with_recovery_code(&envelope.slots, code)?;
+        key.open_sealed(&envelope.sealed)?;
+        self.key.replace(Some(key));
+        self.slots.replace(envelope.slots);
+        Ok(())
+    }
+
+    /// Re-encrypts the opened `payload` under a new data key, so that slots of the old key,
+    /// such as used recovery codes or those kept in backups, no longer open it. The passphrase
+    /// becomes `new_passphrase`, or stays the current one once it is checked; the keyfile this
+    /// unlocker holds is wrapped again, and `recovery` new codes replace all old ones (see
+    /// `take_recovery_codes`). Returns the new envelope and the number of keyfile slots dropped
+    /// because their keyfile was not given.
+    pub fn rotate_key(
+        &self,
+        payload: &str,
+        new_passphrase: Option<&str>,
+        recovery: usize,
+    ) -> Result<(String, usize), String> {
+        let plaintext = self.with_key(|key| {
+            serde_json::to_vec(&key.open(payload)?)
+                .map(Zeroizing::new)
+                .map_err(|err| err.to_string())
+        })?;
+        let old_slots = self.slots();
+        let of_kind = |kind| {
+            old_slots
+                .iter()
+                .filter(move |slot: &&KeySlot| slot.kind == kind)
+        };
+        let key = DataKey::generate();
+        let mut slots = Vec::new();
+        match new_passphrase {
+            Some(passphrase) => slots.push(key.wrap_passphrase(passphrase)?),
+            None if of_kind(SlotKind::Passphrase).next().is_some() => {
+                // A file unlocked with its keyfile has not checked the passphrase yet.
+                let passphrase = self.passphrase()?;
+                let mut valid = false;
+                for slot in of_kind(SlotKind::Passphrase) {
+                    valid |= slot.unwrap_secret(passphrase)?.is_some();
+                }
+                if !valid {
+                    return Err("Invalid passphrase; the data file was not changed.".into());
+                }
+                slots.push(key.wrap_passphrase(passphrase)?);
+            }
+            None => {}
+        }
+        let keyfile = self.keyfile().ok().flatten();
+        let mut dropped = 0;
+        for slot in of_kind(SlotKind::Keyfile) {
+            match keyfile {
+                Some(keyfile) if slot.matches_keyfile(keyfile) => {
+                    slots.push(key.wrap_keyfile(keyfile)?)
+                }
+                _ => dropped += 1,
+            }
+        }
+        let (recovery_slots, codes) = key.recovery_slots(recovery)?;
+        slots.extend(recovery_slots);
+        if slots.is_empty() {
+            return Err("A data file needs at least one way to unlock it.".into());
+        }
+        let rotated = envelope_json(slots.clone(), key.seal(&plaintext)?)?;
+        self.key.replace(Some(key));
+        self.slots.replace(slots);
+        self.recovery_codes.replace(codes);
+        Ok((rotated, dropped))
+    }
+
+    /// Recovery codes generated for a new data file, to be shown once after it is saved.
+    pub fn take_recovery_codes(&self) -> Vec<String> {
+        self.recovery_codes.take()
+    }
 
+    pub fn slots(&self) -> Vec<KeySlot> {
+        self.slots.borrow().clone()
+    }
+
+    /// The unlocked data key, for handing to the agent.
+    pub fn into_key(self) -> Option<DataKey> {
+        self.key.into_inner()
+    }
+}
+
+/// Finds the recovery slot that `code` unlocks and returns the data key.
+fn unlock_with_recovery_code(slots: &[KeySlot], code: &str) -> Result<DataKey, String> {
+    let code = normalize_recovery_code(code);
+    if code.len() != RECOVERY_CODE_LEN {
+        return Err(format!(
+            "A recovery code has {} letters and digits, like ABCD-EFGH-JKLM-NPQR.",
+            RECOVERY_CODE_LEN
+        ));
+    }
+    for slot in slots {
+        if slot.kind == SlotKind::Recovery
+            && let Some(key) = slot.unwrap_secret(&code)?
+        {
+            return Ok(key);
+        }
+    }
+    Err("That recovery code does not unlock this data file, or it was already used.".into())
+}
+
+fn generate_recovery_code() -> String {
+    let mut code = String::new();
+    for (idx, byte) in random_bytes(RECOVERY_CODE_LEN).into_iter().enumerate() {
+        if idx > 0 && idx % 4 == 0 {
+            code.push('-');
+        }
+        // 256 is a multiple of the 32-letter alphabet, so every letter is equally likely.
+        code.push(RECOVERY_ALPHABET[byte as usize % RECOVERY_ALPHABET.len()] as char);
+    }
+    code
+}
+
+/// Codes are accepted in any case, with or without dashes and spaces.
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Lists the key slots of an envelope; they are stored in the clear, so no key is needed.
+pub fn envelope_slots(payload: &str) -> Result<Vec<KeySlot>, String> {
+    Ok(parse_envelope(payload)?.slots)
+}
+
+/// Replaces the key slots of an envelope while keeping its encrypted data, which is how the
+/// unlock methods change without re-encrypting the store. Files without slots are upgraded.
+pub fn replace_slots(payload: &str, slots: Vec<KeySlot>) -> Result<String, String> {
+    if slots.is_empty() {
+        return Err("A data file needs at least one way to unlock it.".into());
+    }
+    envelope_json(slots, parse_envelope(payload)?.sealed)
+}
+
+fn envelope_json(slots: Vec<KeySlot>, sealed: SealedData) -> Result<String, String> {
     let envelope = EncryptedStore {
         version: ENVELOPE_VERSION,
-        kdf,
         cipher: CIPHER_NAME.to_string(),
-        salt: general_purpose::STANDARD.encode(salt),
-        nonce: general_purpose::STANDARD.encode(nonce_bytes),
-        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        kdf: None,
+        salt: None,
+        slots,
+        sealed,
     };
-
     serde_json::to_string_pretty(&envelope).map_err(|err| err.to_string())
 }
 
-pub fn decrypt_store(payload: &str, passphrase: &str) -> Result<Store, String> {
+fn check_passphrase(passphrase: &str) -> Result<(), String> {
     if passphrase.trim().is_empty() {
         return Err("Passphrase cannot be empty.".into());
     }
+    Ok(())
+}
+
+/// Whether a data file holds an encrypted envelope rather than plain store JSON. Anything that
+/// is not JSON is treated as encrypted, so that opening it reports the error.
+pub fn is_encrypted(payload: &str) -> bool {
+    serde_json::from_str::<Value>(payload).map_or(true, |value| is_envelope(&value))
+}
+
+fn is_envelope(value: &Value) -> bool {
+    value.get("cipher").is_some() && value.get("ciphertext").is_some()
+}
+
+fn parse_envelope(payload: &str) -> Result<EncryptedStore, String> {
+    serde_json::from_str(payload)
+        .map_err(|err| err.to_string())
+        .and_then(check_envelope)
+}
 
-    let envelope: EncryptedStore = serde_json::from_str(payload).map_err(|err| err.to_string())?;
-    if envelope.version != ENVELOPE_VERSION {
+fn check_envelope(envelope: EncryptedStore) -> Result<EncryptedStore, String> {
+    if envelope.version == 0 || envelope.version > ENVELOPE_VERSION {
         return Err(format!("Unsupported data version {}.", envelope.version));
     }
     if envelope.cipher != CIPHER_NAME {
         return Err(format!("Unsupported cipher {}.", envelope.cipher));
     }
-    if envelope.kdf.name != KDF_NAME {
-        return Err(format!("Unsupported KDF {}.", envelope.kdf.name));
+    let legacy = envelope.kdf.is_some() && envelope.salt.is_some();
+    let valid = match envelope.version {
+        1 => legacy && envelope.slots.is_empty(),
+        _ => !legacy && !envelope.slots.is_empty(),
+    };
+    if !valid {
+        return Err("Invalid data file envelope.".into());
     }
+    Ok(envelope)
+}
 
-    let salt = general_purpose::STANDARD
-        .decode(envelope.salt)
-        .map_err(|_| "Invalid salt encoding.".to_string())?;
-    let nonce_bytes = general_purpose::STANDARD
-        .decode(envelope.nonce)
-        .map_err(|_| "Invalid nonce encoding.".to_string())?;
-    let ciphertext = general_purpose::STANDARD
-        .decode(envelope.ciphertext)
-        .map_err(|_| "Invalid ciphertext encoding.".to_string())?;
-    if salt.is_empty() {
-        return Err("Invalid salt length.".into());
+fn check_kdf(kdf: &KdfConfig) -> Result<(), String> {
+    if kdf.name != KDF_NAME {
+        return Err(format!("Unsupported KDF {}.", kdf.name));
     }
-    if nonce_bytes.len() != 24 {
-        return Err("Invalid nonce length.".into());
+    Ok(())
+}
+
+fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = random_bytes(24);
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "Encryption failed.".to_string())?;
+    Ok((nonce, ciphertext))
+}
+
+fn decrypt(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    if nonce.len() != 24 {
+        return None;
     }
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    cipher.decrypt(XNonce::from_slice(nonce), ciphertext).ok()
+}
 
-    let key = derive_key(passphrase, &salt, &envelope.kdf)?;
-    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
-    let nonce = XNonce::from_slice(&nonce_bytes);
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext.as_ref())
-        .map_err(|_| "Invalid passphrase or corrupted data file.".to_string())?;
+fn key_from_bytes(bytes: &[u8]) -> Option<Zeroizing<[u8; 32]>> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    (bytes.len() == key.len()).then(|| {
+        key.copy_from_slice(bytes);
+        key
+    })
+}
 
-    serde_json::from_slice(&plaintext).map_err(|err| err.to_string())
+fn decode(value: &str, what: &str) -> Result<Vec<u8>, String> {
+    general_purpose::STANDARD
+        .decode(value)
+        .map_err(|_| format!("Invalid {} encoding.", what))
 }
 
 fn default_kdf() -> KdfConfig {
@@ -132,13 +857,17 @@ fn default_kdf() -> KdfConfig {
     }
 }
 
-fn derive_key(passphrase: &str, salt: &[u8], kdf: &KdfConfig) -> Result<[u8; 32], String> {
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    kdf: &KdfConfig,
+) -> Result<Zeroizing<[u8; 32]>, String> {
     let params =
         Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, None).map_err(|err| err.to_string())?;
     let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
-    let mut key = [0u8; 32];
+    let mut key = Zeroizing::new([0u8; 32]);
     argon2
-        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut_slice())
         .map_err(|err| err.to_string())?;
     Ok(key)
 }
@@ -154,13 +883,46 @@ mod tests {
     use super::*;
     use crate::model::{Segment, Task};
 
+    fn unlocker(passphrase: &str, keyfile: Option<PathBuf>) -> Unlocker {
+        let unlocker = Unlocker::new(PassphraseSources::default(), keyfile, false, None);
+        let _ = unlocker.passphrase.set(passphrase.into());
+        unlocker
+    }
+
     #[test]
     fn encrypt_decrypt_roundtrip() {
         let store = Store {
-            version: 1,
             tasks: vec![Task {
                 id: "id".into(),
                 name: "Task".into(),
+                tags: Vec::new(),
+                project_id: None,
+                archived: false,
+                created_at: chrono::Utc::now(),
+                closed_at: None,
+                segments: vec![Segment {
+                    start_at: chrono::Utc::now(),
+                    end_at: None,
+                }],
+            }],
+            ..Store::default()
+        };
+
+        let payload = unlocker("secret-passphrase", None).seal(&store).unwrap();
+        let decoded = unlocker("secret-passphrase", None).open(&payload).unwrap();
+        assert_eq!(decoded["tasks"].as_array().unwrap().len(), 1);
+        assert_eq!(decoded["tasks"][0]["name"], "Task");
+    }
+
+    #[test]
+    fn key_slots_unlock_one_data_key_and_rekey_keeps_the_ciphertext() {
+        let store = Store {
+            tasks: vec![Task {
+                id: "id".into(),
+                name: "Task".into(),
+                tags: Vec::new(),
+                project_id: None,
+                archived: false,
                 created_at: chrono::Utc::now(),
                 closed_at: None,
                 segments: vec![Segment {
@@ -168,11 +930,179 @@ mod tests {
                     end_at: None,
                 }],
             }],
+            ..Store::default()
+        };
+        let ciphertext = |payload: &str| parse_envelope(payload).unwrap().sealed.ciphertext;
+
+        let creator = unlocker("secret-passphrase", None);
+        let payload = creator.seal(&store).unwrap();
+        assert_eq!(creator.take_recovery_codes().len(), RECOVERY_CODE_COUNT);
+        assert_eq!(
+            envelope_slots(&payload).unwrap().len(),
+            1 + RECOVERY_CODE_COUNT
+        );
+        let reader = unlocker("secret-passphrase", None);
+        let decoded = reader.open(&payload).unwrap();
+        assert_eq!(decoded["tasks"][0]["name"], "Task");
+        assert!(unlocker("wrong", None).open(&payload).is_err());
+
+        // Rekeying and adding a keyfile only rewrite the slots.
+        let path = std::env::temp_dir().join(format!("ttt-key-{}", uuid::Uuid::new_v4()));
+        let keyfile = Keyfile::create(&path).unwrap();
+        assert!(Keyfile::create(&path).is_err());
+        let mut slots = reader.slots();
+        slots.retain(|slot| slot.kind != SlotKind::Passphrase);
+        slots.push(
+            reader
+                .with_key(|key| key.wrap_passphrase("new-passphrase"))
+                .unwrap(),
+        );
+        slots.push(reader.with_key(|key| key.wrap_keyfile(&keyfile)).unwrap());
+        let rekeyed = replace_slots(&payload, slots).unwrap();
+        assert_eq!(ciphertext(&rekeyed), ciphertext(&payload));
+        assert!(unlocker("secret-passphrase", None).open(&rekeyed).is_err());
+        assert!(unlocker("new-passphrase", None).open(&rekeyed).is_ok());
+        let by_keyfile = Unlocker::new(
+            PassphraseSources::default(),
+            Some(path.clone()),
+            false,
+            None,
+        );
+        by_keyfile.prepare(&path.with_extension("missing")).unwrap();
+        assert_eq!(by_keyfile.open(&rekeyed).unwrap(), decoded);
+        assert!(replace_slots(&rekeyed, Vec::new()).is_err());
+        fs::remove_file(&path).unwrap();
+
+        // Each recovery code unlocks the same data key.
+        let (slots, codes) = reader.with_key(|key| key.recovery_slots(2)).unwrap();
+        assert_eq!(codes[0].len(), 19);
+        let typed = codes[1].to_lowercase().replace('-', " ");
+        let key = unlock_with_recovery_code(&slots, &typed).unwrap();
+        assert_eq!(key.open(&rekeyed).unwrap(), decoded);
+        assert!(unlock_with_recovery_code(&slots[..1], &codes[1]).is_err());
+
+        // A file from before key slots keeps its derived key as the data key.
+        let kdf = default_kdf();
+        let salt = random_bytes(SALT_LEN);
+        let legacy_key = DataKey {
+            key: derive_key("secret-passphrase", &salt, &kdf).unwrap(),
+        };
+        let legacy = serde_json::to_string(&EncryptedStore {
+            version: 1,
+            cipher: CIPHER_NAME.into(),
+            kdf: Some(kdf),
+            salt: Some(general_purpose::STANDARD.encode(&salt)),
+            slots: Vec::new(),
+            sealed: legacy_key
+                .seal(&serde_json::to_vec(&store).unwrap())
+                .unwrap(),
+        })
+        .unwrap();
+        let reader = unlocker("secret-passphrase", None);
+        assert!(reader.open(&legacy).is_ok());
+        let upgraded = reader.seal(&store).unwrap();
+        assert_eq!(envelope_slots(&upgraded).unwrap().len(), 1);
+        assert!(legacy_key.open(&upgraded).is_ok());
+    }
+
+    #[test]
+    fn using_a_recovery_code_rotates_the_data_key() {
+        let store = Store::default();
+        let creator = unlocker("secret-passphrase", None);
+        let backup = creator.seal(&store).unwrap();
+        let codes = creator.take_recovery_codes();
+
+        let recovering = unlocker("unused", None);
+        assert!(recovering.open_with_recovery_code(&backup, "ABCD").is_err());
+        recovering
+            .open_with_recovery_code(&backup, &codes[0])
+            .unwrap();
+        let (rotated, dropped) = recovering
+            .rotate_key(&backup, Some("new-passphrase"), 2)
+            .unwrap();
+        assert_eq!(dropped, 0);
+        assert_eq!(recovering.take_recovery_codes().len(), 2);
+        assert!(unlocker("new-passphrase", None).open(&rotated).is_ok());
+        assert!(unlocker("secret-passphrase", None).open(&rotated).is_err());
+
+        // The old codes, and the key they unwrap from the backup, no longer open the file.
+        let slots = envelope_slots(&rotated).unwrap();
+        assert!(unlock_with_recovery_code(&slots, &codes[1]).is_err());
+        let old_key = unlock_with_recovery_code(&envelope_slots(&backup).unwrap(), &codes[1]);
+        assert!(old_key.unwrap().open(&rotated).is_err());
+    }
+
+    #[test]
+    fn plain_files_are_read_and_written_without_a_key() {
+        let no_secrets = || Unlocker::new(PassphraseSources::default(), None, false, None);
+        let writer = no_secrets().with_encryption(Encryption::None);
+        let payload = writer.seal(&Store::default()).unwrap();
+        assert!(!is_encrypted(&payload));
+        assert!(is_encrypted("not json"));
+
+        // Opening switches to the file's mode, so the next save stays plain.
+        let path = std::env::temp_dir().join(format!("ttt-plain-{}.json", uuid::Uuid::new_v4()));
+        fs::write(&path, &payload).unwrap();
+        let reader = no_secrets();
+        reader.prepare(&path).unwrap();
+        let value = reader.open(&payload).unwrap();
+        assert_eq!(value["version"], crate::model::STORE_VERSION);
+        assert_eq!(reader.encryption(), Encryption::None);
+        assert_eq!(reader.seal(&Store::default()).unwrap(), payload);
+        fs::remove_file(&path).unwrap();
+
+        // Plain JSON without the marker only opens when the config asks for plain files.
+        let unmarked = serde_json::to_string(&Store::default()).unwrap();
+        assert!(!is_encrypted(&unmarked));
+        assert!(no_secrets().open(&unmarked).is_err());
+        let expecting_plain = no_secrets().with_encryption(Encryption::None);
+        assert_eq!(expecting_plain.open(&unmarked).unwrap(), value);
+        assert_eq!(expecting_plain.seal(&Store::default()).unwrap(), payload);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn passphrase_sources_are_tried_in_priority_order() {
+        let path = std::env::temp_dir().join(format!("ttt-pass-{}", uuid::Uuid::new_v4()));
+        fs::write(&path, "from-file\n").unwrap();
+        let command = Some("printf 'from-command\\nsecond line\\n'".to_string());
+
+        let sources = PassphraseSources {
+            file: Some(path.clone()),
+            fd: None,
+            command: command.clone(),
+        };
+        assert_eq!(read_passphrase(&sources, true).unwrap(), "from-file");
+
+        let sources = PassphraseSources {
+            file: None,
+            fd: None,
+            command,
         };
+        assert_eq!(read_passphrase(&sources, false).unwrap(), "from-command");
 
-        let payload = encrypt_store(&store, "secret-passphrase").unwrap();
-        let decoded = decrypt_store(&payload, "secret-passphrase").unwrap();
-        assert_eq!(decoded.tasks.len(), 1);
-        assert_eq!(decoded.tasks[0].name, "Task");
+        let failing = PassphraseSources {
+            file: None,
+            fd: None,
+            command: Some("exit 3".into()),
+        };
+        assert!(
+            read_passphrase(&failing, false)
+                .unwrap_err()
+                .contains("failed")
+        );
+
+        fs::write(&path, "\n").unwrap();
+        let empty = PassphraseSources {
+            file: Some(path.clone()),
+            fd: None,
+            command: None,
+        };
+        assert!(
+            read_passphrase(&empty, false)
+                .unwrap_err()
+                .contains("empty")
+        );
+        fs::remove_file(&path).unwrap();
     }
 }
@@ -0,0 +1,321 @@
+use std::fmt;
+use std::mem::{Discriminant, discriminant};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::model::Store;
+use crate::time::format_datetime_local;
+use crate::validate::overlapping_segments;
+
+/// Paused tasks whose last segment ended longer ago than this are treated as forgotten.
+pub const STALE_PAUSE_DAYS: i64 = 14;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fix {
+    DropSegment {
+        task: usize,
+        segment: usize,
+    },
+    CloseSegment {
+        task: usize,
+        segment: usize,
+        at: DateTime<Utc>,
+    },
+    TrimSegmentStart {
+        task: usize,
+        segment: usize,
+        at: DateTime<Utc>,
+    },
+    CloseTask {
+        task: usize,
+        at: DateTime<Utc>,
+    },
+}
+
+#[derive(Debug)]
+pub struct Anomaly {
+    pub task_id: String,
+    pub segment: Option<usize>,
+    pub span: Option<(DateTime<Utc>, Option<DateTime<Utc>>)>,
+    pub problem: String,
+    pub fix: Fix,
+}
+
+/// Identifies an anomaly across rescans: fixes shift segment positions, so it uses the task id
+/// and the segment's start and end instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnomalyKey {
+    task_id: String,
+    span: Option<(DateTime<Utc>, Option<DateTime<Utc>>)>,
+    fix: Discriminant<Fix>,
+}
+
+impl Anomaly {
+    pub fn key(&self) -> AnomalyKey {
+        AnomalyKey {
+            task_id: self.task_id.clone(),
+            span: self.span,
+            fix: discriminant(&self.fix),
+        }
+    }
+
+    pub fn fix_description(&self) -> String {
+        match self.fix {
+            Fix::DropSegment { segment, .. } => format!("drop segment {}", segment + 1),
+            Fix::CloseSegment { segment, at, .. } => format!(
+                "close segment {} at {}",
+                segment + 1,
+                format_datetime_local(at)
+            ),
+            Fix::TrimSegmentStart { segment, at, .. } => format!(
+                "move the start of segment {} to {}",
+                segment + 1,
+                format_datetime_local(at)
+            ),
+            Fix::CloseTask { at, .. } => format!("close the task at {}", format_datetime_local(at)),
+        }
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.segment {
+            Some(segment) => write!(
+                f,
+                "task {} segment {}: {}",
+                self.task_id,
+                segment + 1,
+                self.problem
+            ),
+            None => write!(f, "task {}: {}", self.task_id, self.problem),
+        }
+    }
+}
+
+pub fn find_anomalies(store: &Store, now: DateTime<Utc>) -> Vec<Anomaly> {
+    let mut anomalies = Vec::new();
+
+    for (task_idx, task) in store.tasks.iter().enumerate() {
+        let anomaly = |segment: Option<usize>, problem: String, fix: Fix| Anomaly {
+            task_id: task.id.clone(),
+            segment,
+            span: segment.map(|idx| (task.segments[idx].start_at, task.segments[idx].end_at)),
+            problem,
+            fix,
+        };
+        for (idx, segment) in task.segments.iter().enumerate() {
+            let drop = Fix::DropSegment {
+                task: task_idx,
+                segment: idx,
+            };
+            match (segment.end_at, task.closed_at) {
+                (Some(end_at), _) if end_at == segment.start_at => {
+                    anomalies.push(anomaly(Some(idx), "has zero length".into(), drop));
+                }
+                (Some(end_at), _) if end_at < segment.start_at => {
+                    anomalies.push(anomaly(Some(idx), "ends before it starts".into(), drop));
+                }
+                (None, Some(closed_at)) => {
+                    let fix = if closed_at > segment.start_at {
+                        Fix::CloseSegment {
+                            task: task_idx,
+                            segment: idx,
+                            at: closed_at,
+                        }
+                    } else {
+                        drop
+                    };
+                    anomalies.push(anomaly(
+                        Some(idx),
+                        "is still open but the task is stopped".into(),
+                        fix,
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        let latest_end = task
+            .segments
+            .iter()
+            .filter_map(|segment| segment.end_at)
+            .max();
+        let has_open_segment = task.segments.iter().any(|segment| segment.end_at.is_none());
+        match (task.closed_at, latest_end) {
+            (Some(closed_at), Some(latest_end)) if closed_at < latest_end => {
+                anomalies.push(anomaly(
+                    None,
+                    format!(
+                        "stopped at {} before its last segment ended",
+                        format_datetime_local(closed_at)
+                    ),
+                    Fix::CloseTask {
+                        task: task_idx,
+                        at: latest_end,
+                    },
+                ));
+            }
+            (None, Some(latest_end))
+                if !has_open_segment && now - latest_end > Duration::days(STALE_PAUSE_DAYS) =>
+            {
+                anomalies.push(anomaly(
+                    None,
+                    format!(
+                        "paused since {} ({} days)",
+                        format_datetime_local(latest_end),
+                        (now - latest_end).num_days()
+                    ),
+                    Fix::CloseTask {
+                        task: task_idx,
+                        at: latest_end,
+                    },
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    for (earlier, later) in overlapping_segments(store) {
+        let earlier_segment = &store.tasks[earlier.task].segments[earlier.segment];
+        let later_segment = &store.tasks[later.task].segments[later.segment];
+        let fix = match earlier_segment.end_at {
+            None => Fix::CloseSegment {
+                task: earlier.task,
+                segment: earlier.segment,
+                at: later_segment.start_at,
+            },
+            Some(end_at)
+                if later_segment
+                    .end_at
+                    .is_some_and(|later_end| later_end <= end_at) =>
+            {
+                Fix::DropSegment {
+                    task: later.task,
+                    segment: later.segment,
+                }
+            }
+            Some(end_at) => Fix::TrimSegmentStart {
+                task: later.task,
+                segment: later.segment,
+                at: end_at,
+            },
+        };
+        anomalies.push(Anomaly {
+            task_id: store.tasks[later.task].id.clone(),
+            segment: Some(later.segment),
+            span: Some((later_segment.start_at, later_segment.end_at)),
+            problem: format!(
+                "overlaps task {} segment {}",
+                store.tasks[earlier.task].id,
+                earlier.segment + 1
+            ),
+            fix,
+        });
+    }
+
+    anomalies
+}
+
+pub fn apply_fix(store: &mut Store, fix: Fix) {
+    match fix {
+        Fix::DropSegment { task, segment } => {
+            store.tasks[task].segments.remove(segment);
+        }
+        Fix::CloseSegment { task, segment, at } => {
+            store.tasks[task].segments[segment].end_at = Some(at);
+        }
+        Fix::TrimSegmentStart { task, segment, at } => {
+            store.tasks[task].segments[segment].start_at = at;
+        }
+        Fix::CloseTask { task, at } => {
+            store.tasks[task].closed_at = Some(at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::model::{Segment, Task};
+    use crate::validate::validate_store;
+
+    #[test]
+    fn applying_every_fix_leaves_a_valid_store() {
+        let nine = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let at = |minutes: i64| nine + Duration::minutes(minutes);
+        let segment = |start: i64, end: Option<i64>| Segment {
+            start_at: at(start),
+            end_at: end.map(at),
+        };
+        let task = |id: &str, closed_at: Option<i64>, segments: Vec<Segment>| Task {
+            id: id.into(),
+            name: id.into(),
+            tags: Vec::new(),
+            project_id: None,
+            archived: false,
+            created_at: nine,
+            closed_at: closed_at.map(at),
+            segments,
+        };
+        let mut store = Store {
+            tasks: vec![
+                task("overlap", Some(60), vec![segment(0, Some(60))]),
+                task(
+                    "late",
+                    Some(90),
+                    vec![segment(30, Some(90)), segment(95, Some(95))],
+                ),
+                task("stopped-open", Some(150), vec![segment(120, None)]),
+                task("paused", None, vec![segment(200, Some(230))]),
+            ],
+            ..Store::default()
+        };
+        let now = at(60 * 24 * 30);
+
+        let mut applied = 0;
+        while let Some(anomaly) = find_anomalies(&store, now).into_iter().next() {
+            apply_fix(&mut store, anomaly.fix);
+            applied += 1;
+            assert!(applied < 20, "fixes did not converge");
+        }
+
+        assert!(validate_store(&store).is_empty());
+        assert_eq!(store.tasks[1].segments.len(), 1);
+        assert_eq!(store.tasks[1].segments[0].start_at, at(60));
+        assert_eq!(store.tasks[2].segments[0].end_at, Some(at(150)));
+        assert_eq!(store.tasks[3].closed_at, Some(at(230)));
+    }
+
+    #[test]
+    fn anomaly_keys_survive_earlier_segments_being_dropped() {
+        let nine = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let empty = |minutes: i64| Segment {
+            start_at: nine + Duration::minutes(minutes),
+            end_at: Some(nine + Duration::minutes(minutes)),
+        };
+        let mut store = Store {
+            tasks: vec![Task {
+                id: "task".into(),
+                name: "task".into(),
+                tags: Vec::new(),
+                project_id: None,
+                archived: false,
+                created_at: nine,
+                closed_at: Some(nine + Duration::hours(1)),
+                segments: vec![empty(0), empty(30)],
+            }],
+            ..Store::default()
+        };
+
+        let before = find_anomalies(&store, nine);
+        let declined = before[1].key();
+        apply_fix(&mut store, before[0].fix);
+        let after = find_anomalies(&store, nine);
+
+        assert_eq!(after.len(), 1);
+        assert_eq!(after[0].key(), declined);
+        assert_ne!(after[0].to_string(), before[1].to_string());
+    }
+}
@@ -1,20 +1,38 @@
 use chrono::{DateTime, Utc};
 
-use crate::model::{SegmentEdit, Store, Task};
+use crate::model::{Segment, SegmentEdit, Store, Task};
 use crate::prompt::{prompt_line, prompt_optional};
 use crate::tasks::{task_status, total_elapsed};
-use crate::time::{format_datetime_local, format_duration};
+use crate::time::{format_datetime_local, format_duration, parse_datetime_input};
 
+pub struct SegmentOperations {
+    pub edit: Vec<String>,
+    pub add: Vec<String>,
+    pub delete: Option<usize>,
+    pub split: Option<String>,
+    pub merge: Option<String>,
+}
+
+impl SegmentOperations {
+    pub fn is_empty(&self) -> bool {
+        self.edit.is_empty()
+            && self.add.is_empty()
+            && self.delete.is_none()
+            && self.split.is_none()
+            && self.merge.is_none()
+    }
+}
+
+/// Resolves `--id`/`--index` or prompts for a task. Indexes and the prompt only cover tasks
+/// whose archived flag equals `archived`; an id matches any task.
 pub fn resolve_task_index(
     store: &Store,
     now: DateTime<Utc>,
     id: Option<String>,
     index: Option<usize>,
+    action: &str,
+    archived: bool,
 ) -> Result<usize, String> {
-    if store.tasks.is_empty() {
-        return Err("No tasks to edit.".into());
-    }
-
     if id.is_some() && index.is_some() {
         return Err("Use either --id or --index, not both.".into());
     }
@@ -27,28 +45,45 @@ pub fn resolve_task_index(
             .ok_or_else(|| format!("No task found with id \"{}\".", id));
     }
 
+    let candidates: Vec<usize> = store
+        .tasks
+        .iter()
+        .enumerate()
+        .filter(|(_, task)| task.archived == archived)
+        .map(|(idx, _)| idx)
+        .collect();
+    if candidates.is_empty() {
+        return Err(format!("No tasks to {}.", action));
+    }
+
     if let Some(index) = index {
-        if index == 0 || index > store.tasks.len() {
+        if index == 0 || index > candidates.len() {
             return Err(format!(
                 "Task index must be between 1 and {}.",
-                store.tasks.len()
+                candidates.len()
             ));
         }
-        return Ok(index - 1);
+        return Ok(candidates[index - 1]);
     }
 
-    prompt_task_selection(store, now)
+    prompt_task_selection(store, now, &candidates, action)
 }
 
-fn prompt_task_selection(store: &Store, now: DateTime<Utc>) -> Result<usize, String> {
-    println!("Select a task to edit:");
-    for (idx, task) in store.tasks.iter().enumerate() {
+fn prompt_task_selection(
+    store: &Store,
+    now: DateTime<Utc>,
+    candidates: &[usize],
+    action: &str,
+) -> Result<usize, String> {
+    println!("Select a task to {}:", action);
+    for (position, &idx) in candidates.iter().enumerate() {
+        let task = &store.tasks[idx];
         let id_short = short_id(&task.id);
         let status = task_status(task);
         let elapsed = format_duration(total_elapsed(task, now));
         println!(
             "{:>3}) [{}] {} ({}) total {}",
-            idx + 1,
+            position + 1,
             status,
             task.name,
             id_short,
@@ -63,13 +98,13 @@ fn prompt_task_selection(store: &Store, now: DateTime<Utc>) -> Result<usize, Str
     let selection: usize = input
         .parse()
         .map_err(|_| "Invalid selection. Enter a number from the list.".to_string())?;
-    if selection == 0 || selection > store.tasks.len() {
+    if selection == 0 || selection > candidates.len() {
         return Err(format!(
             "Task index must be between 1 and {}.",
-            store.tasks.len()
+            candidates.len()
         ));
     }
-    Ok(selection - 1)
+    Ok(candidates[selection - 1])
 }
 
 fn short_id(id: &str) -> &str {
@@ -84,9 +119,7 @@ pub fn edit_task_interactive(task: &mut Task, now: DateTime<Utc>) -> Result<(),
     }
 
     let created_label = format_datetime_local(task.created_at);
-    if let Some(input) =
-        prompt_optional(&format!("Created at [{}] (RFC3339/now): ", created_label))?
-    {
+    if let Some(input) = prompt_optional(&format!("Created at [{}] (time/now): ", created_label))? {
         task.created_at = parse_datetime_input(&input, now, "created at")?;
     }
 
@@ -94,23 +127,21 @@ pub fn edit_task_interactive(task: &mut Task, now: DateTime<Utc>) -> Result<(),
         Some(closed_at) => format_datetime_local(closed_at),
         None => "open".to_string(),
     };
-    if let Some(input) = prompt_optional(&format!(
-        "Closed at [{}] (RFC3339/now/open): ",
-        closed_label
-    ))? {
+    if let Some(input) =
+        prompt_optional(&format!("Closed at [{}] (time/now/open): ", closed_label))?
+    {
         task.closed_at = parse_optional_datetime_input(&input, now, "closed at")?;
     }
 
     if task.segments.is_empty() {
         println!("No segments to edit.");
-        return Ok(());
+    } else {
+        println!("Segments:");
     }
-
-    println!("Segments:");
     for (idx, segment) in task.segments.iter_mut().enumerate() {
         let start_label = format_datetime_local(segment.start_at);
         if let Some(input) = prompt_optional(&format!(
-            "Segment {} start [{}] (RFC3339/now): ",
+            "Segment {} start [{}] (time/now): ",
             idx + 1,
             start_label
         ))? {
@@ -122,7 +153,7 @@ pub fn edit_task_interactive(task: &mut Task, now: DateTime<Utc>) -> Result<(),
             None => "open".to_string(),
         };
         if let Some(input) = prompt_optional(&format!(
-            "Segment {} end [{}] (RFC3339/now/open): ",
+            "Segment {} end [{}] (time/now/open): ",
             idx + 1,
             end_label
         ))? {
@@ -130,15 +161,85 @@ pub fn edit_task_interactive(task: &mut Task, now: DateTime<Utc>) -> Result<(),
         }
     }
 
+    loop {
+        print_segments(task, now);
+        let action =
+            prompt_line("Segment action ([a]dd, [d]elete, [s]plit, [m]erge, Enter to finish): ")?;
+        if action.is_empty() {
+            break;
+        }
+        if let Err(err) = apply_interactive_segment_action(task, &action, now) {
+            println!("{}", err);
+        }
+    }
+
     Ok(())
 }
 
+fn apply_interactive_segment_action(
+    task: &mut Task,
+    action: &str,
+    now: DateTime<Utc>,
+) -> Result<(), String> {
+    match action.to_ascii_lowercase().as_str() {
+        "a" | "add" => {
+            let start_at = parse_datetime_input(
+                &prompt_line("New segment start (time): ")?,
+                now,
+                "segment start",
+            )?;
+            let end_at = parse_datetime_input(
+                &prompt_line("New segment end (time): ")?,
+                now,
+                "segment end",
+            )?;
+            add_segment(task, start_at, end_at)
+        }
+        "d" | "delete" => {
+            let index = parse_segment_number(&prompt_line("Segment to delete: ")?)?;
+            delete_segment(task, index)
+        }
+        "s" | "split" => {
+            let index = parse_segment_number(&prompt_line("Segment to split: ")?)?;
+            let at = parse_datetime_input(&prompt_line("Split at (time): ")?, now, "split")?;
+            split_segment(task, index, at, now)
+        }
+        "m" | "merge" => {
+            let first = parse_segment_number(&prompt_line("First segment: ")?)?;
+            let second = parse_segment_number(&prompt_line("Second segment: ")?)?;
+            merge_segments(task, first, second)
+        }
+        _ => Err("Unknown action. Use a, d, s or m.".into()),
+    }
+}
+
+fn print_segments(task: &Task, now: DateTime<Utc>) {
+    if task.segments.is_empty() {
+        println!("No segments.");
+        return;
+    }
+    for (idx, segment) in task.segments.iter().enumerate() {
+        let end_label = match segment.end_at {
+            Some(end_at) => format_datetime_local(end_at),
+            None => "open".to_string(),
+        };
+        let seconds = (segment.end_at.unwrap_or(now) - segment.start_at).num_seconds();
+        println!(
+            "{:>3}) {} - {} ({})",
+            idx + 1,
+            format_datetime_local(segment.start_at),
+            end_label,
+            format_duration(seconds)
+        );
+    }
+}
+
 pub fn apply_task_edits(
     task: &mut Task,
     name: Option<String>,
     created_at: Option<String>,
     closed_at: Option<String>,
-    segment_edits: Vec<String>,
+    segments: SegmentOperations,
     now: DateTime<Utc>,
 ) -> Result<(), String> {
     if let Some(name) = name {
@@ -156,22 +257,130 @@ pub fn apply_task_edits(
         task.closed_at = parse_optional_datetime_input(&closed_at, now, "closed at")?;
     }
 
-    for edit in segment_edits {
+    // Indexes refer to the segment list as shown before this edit: in-place edits come first,
+    // then the single delete/split/merge, and added segments last.
+    for edit in segments.edit {
         let (index, start_at, end_at) = parse_segment_edit(&edit, now)?;
-        if index == 0 || index > task.segments.len() {
-            return Err(format!(
-                "Segment index must be between 1 and {}.",
-                task.segments.len()
-            ));
-        }
-        let segment = &mut task.segments[index - 1];
+        let position = segment_position(task, index)?;
+        let segment = &mut task.segments[position];
         segment.start_at = start_at;
         segment.end_at = end_at;
     }
 
+    if let Some(index) = segments.delete {
+        delete_segment(task, index)?;
+    }
+
+    if let Some(split) = segments.split {
+        let (index, at) = split
+            .split_once(',')
+            .ok_or("Segment split must be in the form INDEX,AT.")?;
+        let at = parse_datetime_input(at, now, "split")?;
+        split_segment(task, parse_segment_number(index)?, at, now)?;
+    }
+
+    if let Some(merge) = segments.merge {
+        let (first, second) = merge
+            .split_once(',')
+            .ok_or("Segment merge must be in the form N,M.")?;
+        merge_segments(
+            task,
+            parse_segment_number(first)?,
+            parse_segment_number(second)?,
+        )?;
+    }
+
+    for add in segments.add {
+        let (start_at, end_at) = add
+            .split_once(',')
+            .ok_or("Segment add must be in the form START,END.")?;
+        let start_at = parse_datetime_input(start_at, now, "segment start")?;
+        let end_at = parse_datetime_input(end_at, now, "segment end")?;
+        add_segment(task, start_at, end_at)?;
+    }
+
+    Ok(())
+}
+
+pub fn add_segment(
+    task: &mut Task,
+    start_at: DateTime<Utc>,
+    end_at: DateTime<Utc>,
+) -> Result<(), String> {
+    if end_at <= start_at {
+        return Err("Segment end must be after its start.".into());
+    }
+    let overlaps = task.segments.iter().any(|segment| {
+        segment.start_at < end_at && segment.end_at.is_none_or(|existing| start_at < existing)
+    });
+    if overlaps {
+        return Err("New segment overlaps an existing segment of this task.".into());
+    }
+    task.segments.push(Segment {
+        start_at,
+        end_at: Some(end_at),
+    });
+    task.segments.sort_by_key(|segment| segment.start_at);
+    Ok(())
+}
+
+pub fn delete_segment(task: &mut Task, index: usize) -> Result<(), String> {
+    let position = segment_position(task, index)?;
+    task.segments.remove(position);
     Ok(())
 }
 
+pub fn split_segment(
+    task: &mut Task,
+    index: usize,
+    at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<(), String> {
+    let position = segment_position(task, index)?;
+    let segment = &mut task.segments[position];
+    if at <= segment.start_at || at >= segment.end_at.unwrap_or(now) {
+        return Err(format!("Split time must fall inside segment {}.", index));
+    }
+    let tail = Segment {
+        start_at: at,
+        end_at: segment.end_at,
+    };
+    segment.end_at = Some(at);
+    task.segments.insert(position + 1, tail);
+    Ok(())
+}
+
+pub fn merge_segments(task: &mut Task, first: usize, second: usize) -> Result<(), String> {
+    let (first, second) = (first.min(second), first.max(second));
+    let first_position = segment_position(task, first)?;
+    let second_position = segment_position(task, second)?;
+    if second_position != first_position + 1 {
+        return Err("Only adjacent segments can be merged.".into());
+    }
+    let merged = task.segments.remove(second_position);
+    let segment = &mut task.segments[first_position];
+    segment.start_at = segment.start_at.min(merged.start_at);
+    segment.end_at = merged.end_at;
+    Ok(())
+}
+
+fn segment_position(task: &Task, index: usize) -> Result<usize, String> {
+    if index == 0 || index > task.segments.len() {
+        return Err(format!(
+            "Segment index must be between 1 and {}.",
+            task.segments.len()
+        ));
+    }
+    Ok(index - 1)
+}
+
+fn parse_segment_number(input: &str) -> Result<usize, String> {
+    input
+        .trim()
+        .parse()
+        .map_err(|_| "Segment index must be a number.".to_string())
+}
+
 fn parse_segment_edit(input: &str, now: DateTime<Utc>) -> Result<SegmentEdit, String> {
     let parts: Vec<&str> = input.splitn(3, ',').collect();
     if parts.len() != 3 {
@@ -185,19 +394,6 @@ fn parse_segment_edit(input: &str, now: DateTime<Utc>) -> Result<SegmentEdit, St
     Ok((index, start_at, end_at))
 }
 
-fn parse_datetime_input(
-    input: &str,
-    now: DateTime<Utc>,
-    label: &str,
-) -> Result<DateTime<Utc>, String> {
-    if input.eq_ignore_ascii_case("now") {
-        return Ok(now);
-    }
-    DateTime::parse_from_rfc3339(input)
-        .map(|dt| dt.with_timezone(&Utc))
-        .map_err(|err| format!("Invalid {} timestamp: {}", label, err))
-}
-
 fn parse_optional_datetime_input(
     input: &str,
     now: DateTime<Utc>,
@@ -208,3 +404,78 @@ fn parse_optional_datetime_input(
     }
     parse_datetime_input(input, now, label).map(Some)
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn task_indexes_skip_tasks_outside_the_archived_selection() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let task = |id: &str, archived: bool| Task {
+            id: id.into(),
+            name: id.into(),
+            tags: Vec::new(),
+            project_id: None,
+            archived,
+            created_at: now,
+            closed_at: Some(now),
+            segments: Vec::new(),
+        };
+        let store = Store {
+            tasks: vec![task("a", true), task("b", false), task("c", true)],
+            ..Store::default()
+        };
+
+        let resolve =
+            |index, archived| resolve_task_index(&store, now, None, Some(index), "edit", archived);
+        assert_eq!(resolve(1, false), Ok(1));
+        assert!(resolve(2, false).is_err());
+        assert_eq!(resolve(2, true), Ok(2));
+        assert_eq!(
+            resolve_task_index(&store, now, Some("a".into()), None, "edit", false),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn segment_operations_split_merge_delete_and_add() {
+        let nine = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let at = |minutes: i64| nine + Duration::minutes(minutes);
+        let mut task = Task {
+            id: "task-1".into(),
+            name: "Review".into(),
+            tags: Vec::new(),
+            project_id: None,
+            archived: false,
+            created_at: nine,
+            closed_at: Some(at(120)),
+            segments: vec![Segment {
+                start_at: nine,
+                end_at: Some(at(120)),
+            }],
+        };
+
+        split_segment(&mut task, 1, at(60), at(180)).unwrap();
+        assert_eq!(task.segments.len(), 2);
+        assert_eq!(task.segments[0].end_at, Some(at(60)));
+        assert_eq!(task.segments[1].start_at, at(60));
+        assert!(split_segment(&mut task, 1, at(90), at(180)).is_err());
+
+        merge_segments(&mut task, 2, 1).unwrap();
+        assert_eq!(task.segments.len(), 1);
+        assert_eq!(task.segments[0].end_at, Some(at(120)));
+
+        assert!(add_segment(&mut task, at(110), at(130)).is_err());
+        add_segment(&mut task, at(-60), at(-30)).unwrap();
+        assert_eq!(task.segments[0].start_at, at(-60));
+        assert!(merge_segments(&mut task, 1, 3).is_err());
+
+        delete_segment(&mut task, 1).unwrap();
+        assert_eq!(task.segments.len(), 1);
+        assert_eq!(task.segments[0].start_at, nine);
+        assert!(delete_segment(&mut task, 2).is_err());
+    }
+}
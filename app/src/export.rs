@@ -0,0 +1,199 @@
+use std::io::Write;
+
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::model::{ReportDay, Store};
+use crate::projects::{task_client_name, task_project_name};
+use crate::report::overlap_window;
+use crate::time::local_date;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub header: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SegmentRow {
+    pub task_id: String,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+    pub client: Option<String>,
+    pub start_at: DateTime<Utc>,
+    pub end_at: Option<DateTime<Utc>>,
+    pub seconds: i64,
+    pub date: NaiveDate,
+    #[serde(skip)]
+    pub segment_index: usize,
+}
+
+pub fn parse_delimiter(input: &str) -> Result<u8, String> {
+    match input {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        value if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => Err("Delimiter must be a single ASCII character or 'tab'.".into()),
+    }
+}
+
+pub fn segment_rows(
+    store: &Store,
+    window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    now: DateTime<Utc>,
+) -> Vec<SegmentRow> {
+    let mut rows = Vec::new();
+    for task in &store.tasks {
+        for (segment_index, segment) in task.segments.iter().enumerate() {
+            let (start_at, end_at) = match window {
+                Some((window_start, window_end)) => {
+                    let Some((start, end)) = overlap_window(segment, window_start, window_end, now)
+                    else {
+                        continue;
+                    };
+                    let end_at = match segment.end_at {
+                        Some(_) => Some(end),
+                        None if end < now => Some(end),
+                        None => None,
+                    };
+                    (start, end_at)
+                }
+                None => (segment.start_at, segment.end_at),
+            };
+            let seconds = (end_at.unwrap_or(now) - start_at).num_seconds().max(0);
+            rows.push(SegmentRow {
+                task_id: task.id.clone(),
+                name: task.name.clone(),
+                tags: task.tags.clone(),
+                project: task_project_name(store, task),
+                client: task_client_name(store, task),
+                start_at,
+                end_at,
+                seconds,
+                date: local_date(start_at),
+                segment_index,
+            });
+        }
+    }
+    rows.sort_by(|a, b| {
+        a.start_at
+            .cmp(&b.start_at)
+            .then_with(|| a.task_id.cmp(&b.task_id))
+    });
+    rows
+}
+
+pub fn write_segments_csv<W: Write>(
+    writer: W,
+    rows: &[SegmentRow],
+    options: CsvOptions,
+) -> Result<(), String> {
+    let mut csv = csv_writer(writer, options);
+    if options.header {
+        csv.write_record([
+            "task_id", "name", "tags", "project", "client", "start_at", "end_at", "seconds", "date",
+        ])
+        .map_err(|err| err.to_string())?;
+    }
+    for row in rows {
+        csv.write_record([
+            row.task_id.clone(),
+            row.name.clone(),
+            row.tags.join(" "),
+            row.project.clone().unwrap_or_default(),
+            row.client.clone().unwrap_or_default(),
+            format_timestamp(row.start_at),
+            row.end_at.map(format_timestamp).unwrap_or_default(),
+            row.seconds.to_string(),
+            row.date.to_string(),
+        ])
+        .map_err(|err| err.to_string())?;
+    }
+    csv.flush().map_err(|err| err.to_string())
+}
+
+pub fn write_report_csv<W: Write>(
+    writer: W,
+    days: &[ReportDay],
+    options: CsvOptions,
+) -> Result<(), String> {
+    let mut csv = csv_writer(writer, options);
+    if options.header {
+        csv.write_record(["date", "name", "start_at", "end_at", "seconds", "amount"])
+            .map_err(|err| err.to_string())?;
+    }
+    for day in days {
+        for entry in &day.entries {
+            csv.write_record([
+                day.date.to_string(),
+                entry.name.clone(),
+                format_timestamp(entry.start_at),
+                format_timestamp(entry.end_at),
+                entry.seconds.to_string(),
+                entry
+                    .amount
+                    .map(|amount| format!("{:.2}", amount))
+                    .unwrap_or_default(),
+            ])
+            .map_err(|err| err.to_string())?;
+        }
+    }
+    csv.flush().map_err(|err| err.to_string())
+}
+
+fn format_timestamp(dt: DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn csv_writer<W: Write>(writer: W, options: CsvOptions) -> csv::Writer<W> {
+    csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .has_headers(false)
+        .from_writer(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::model::{Segment, Task};
+
+    #[test]
+    fn segments_csv_quotes_fields_and_honours_options() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2025, 1, 1, 9, 45, 0).unwrap();
+        let store = Store {
+            tasks: vec![Task {
+                id: "task-1".into(),
+                name: "Review; part 1".into(),
+                tags: vec!["acme".into(), "review".into()],
+                project_id: None,
+                archived: false,
+                created_at: start,
+                closed_at: Some(end),
+                segments: vec![Segment {
+                    start_at: start,
+                    end_at: Some(end),
+                }],
+            }],
+            ..Store::default()
+        };
+
+        let rows = segment_rows(&store, None, end);
+        let mut output = Vec::new();
+        let options = CsvOptions {
+            delimiter: parse_delimiter(";").unwrap(),
+            header: false,
+        };
+        write_segments_csv(&mut output, &rows, options).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert_eq!(
+            text.trim_end(),
+            format!(
+                "task-1;\"Review; part 1\";acme review;;;2025-01-01T09:00:00Z;2025-01-01T09:45:00Z;2700;{}",
+                local_date(start)
+            )
+        );
+    }
+}
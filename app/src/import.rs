@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use clap::ValueEnum;
+use uuid::Uuid;
+
+use crate::interop::{parse_timewarrior, parse_watson};
+use crate::model::{Segment, Store, Task};
+use crate::projects::{add_client, add_project, find_client_index, find_project_index};
+use crate::tasks::{current_task_state, normalize_tag};
+use crate::time::local_to_utc;
+
+const HARVEST_DAY_START_HOUR: u32 = 9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ImportSource {
+    Toggl,
+    Clockify,
+    Harvest,
+    Timewarrior,
+    Watson,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CsvSource {
+    Toggl,
+    Clockify,
+    Harvest,
+}
+
+#[derive(Debug)]
+pub struct ImportedEntry {
+    pub line: usize,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+    pub client: Option<String>,
+    pub start_at: DateTime<Utc>,
+    pub end_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub duplicates: usize,
+    pub skipped: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+pub fn parse_import(
+    source: ImportSource,
+    path: &Path,
+) -> Result<(Vec<ImportedEntry>, Vec<String>), String> {
+    let read = |path: &Path| {
+        fs::read_to_string(path)
+            .map_err(|err| format!("Could not read {}: {}", path.display(), err))
+    };
+    let open = |path: &Path| {
+        fs::File::open(path).map_err(|err| format!("Could not open {}: {}", path.display(), err))
+    };
+    match source {
+        ImportSource::Toggl => parse_csv(CsvSource::Toggl, open(path)?),
+        ImportSource::Clockify => parse_csv(CsvSource::Clockify, open(path)?),
+        ImportSource::Harvest => parse_csv(CsvSource::Harvest, open(path)?),
+        ImportSource::Timewarrior => Ok(parse_timewarrior(&read(path)?)),
+        ImportSource::Watson => {
+            // Watson keeps the running frame in a "state" file next to "frames".
+            let state_path = path.with_file_name("state");
+            let state = if state_path.exists() {
+                Some(read(&state_path)?)
+            } else {
+                None
+            };
+            parse_watson(&read(path)?, state.as_deref())
+        }
+    }
+}
+
+fn parse_csv<R: Read>(
+    source: CsvSource,
+    reader: R,
+) -> Result<(Vec<ImportedEntry>, Vec<String>), String> {
+    let mut csv = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers: Vec<String> = csv
+        .headers()
+        .map_err(|err| format!("Could not read CSV header: {}", err))?
+        .iter()
+        .map(|header| header.trim().trim_start_matches('\u{feff}').to_lowercase())
+        .collect();
+    let columns = Columns::new(headers);
+    columns.require(source)?;
+
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    let mut harvest_cursor: HashMap<NaiveDate, DateTime<Utc>> = HashMap::new();
+    for (idx, record) in csv.records().enumerate() {
+        let line = idx + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                skipped.push(format!("line {}: {}", line, err));
+                continue;
+            }
+        };
+        let row = Row {
+            columns: &columns,
+            record: &record,
+        };
+        let parsed = match source {
+            CsvSource::Toggl => parse_toggl_row(&row, line),
+            CsvSource::Clockify => parse_clockify_row(&row, line),
+            CsvSource::Harvest => parse_harvest_row(&row, line, &mut harvest_cursor),
+        };
+        match parsed {
+            Ok(entry) => entries.push(entry),
+            Err(reason) => skipped.push(format!("line {}: {}", line, reason)),
+        }
+    }
+    Ok((entries, skipped))
+}
+
+pub fn apply_import(store: &mut Store, entries: Vec<ImportedEntry>) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+    // Intervals with the same name, tags and project become segments of one task.
+    let mut grouped: HashMap<(String, Vec<String>, Option<String>), usize> = HashMap::new();
+    for entry in entries {
+        if is_duplicate(store, &entry) {
+            summary.duplicates += 1;
+            continue;
+        }
+        if entry.end_at.is_none()
+            && let Some((idx, _)) = current_task_state(store)
+        {
+            summary.conflicts.push(format!(
+                "line {}: \"{}\" is running but task \"{}\" is already active or paused",
+                entry.line, entry.name, store.tasks[idx].name
+            ));
+            continue;
+        }
+        if let Some(existing) = find_overlap(store, entry.start_at, entry.end_at) {
+            summary.conflicts.push(format!(
+                "line {}: \"{}\" overlaps task \"{}\" ({})",
+                entry.line, entry.name, existing.name, existing.id
+            ));
+            continue;
+        }
+        let project_id =
+            match link_project(store, entry.project.as_deref(), entry.client.as_deref()) {
+                Ok(project_id) => project_id,
+                Err(err) => {
+                    summary
+                        .skipped
+                        .push(format!("line {}: {}", entry.line, err));
+                    continue;
+                }
+            };
+        let segment = Segment {
+            start_at: entry.start_at,
+            end_at: entry.end_at,
+        };
+        let key = (entry.name, entry.tags, project_id);
+        if let Some(&idx) = grouped.get(&key) {
+            let task = &mut store.tasks[idx];
+            task.segments.push(segment);
+            task.segments.sort_by_key(|segment| segment.start_at);
+            task.created_at = task.segments[0].start_at;
+            task.closed_at = task.segments.last().and_then(|segment| segment.end_at);
+        } else {
+            grouped.insert(key.clone(), store.tasks.len());
+            let (name, tags, project_id) = key;
+            store.tasks.push(Task {
+                id: Uuid::new_v4().to_string(),
+                name,
+                tags,
+                project_id,
+                archived: false,
+                created_at: entry.start_at,
+                closed_at: entry.end_at,
+                segments: vec![segment],
+            });
+        }
+        summary.imported += 1;
+    }
+    summary
+}
+
+fn is_duplicate(store: &Store, entry: &ImportedEntry) -> bool {
+    store.tasks.iter().any(|task| {
+        task.name == entry.name
+            && task
+                .segments
+                .iter()
+                .any(|segment| segment.start_at == entry.start_at && segment.end_at == entry.end_at)
+    })
+}
+
+fn find_overlap(
+    store: &Store,
+    start_at: DateTime<Utc>,
+    end_at: Option<DateTime<Utc>>,
+) -> Option<&Task> {
+    store.tasks.iter().find(|task| {
+        task.segments.iter().any(|segment| {
+            let segment_end = segment.end_at.unwrap_or(DateTime::<Utc>::MAX_UTC);
+            let entry_end = end_at.unwrap_or(DateTime::<Utc>::MAX_UTC);
+            segment.start_at < entry_end && start_at < segment_end
+        })
+    })
+}
+
+fn link_project(
+    store: &mut Store,
+    project: Option<&str>,
+    client: Option<&str>,
+) -> Result<Option<String>, String> {
+    let Some(project) = project else {
+        return Ok(None);
+    };
+    if let Some(client) = client
+        && find_client_index(store, client).is_none()
+    {
+        add_client(store, client, None)?;
+    }
+    if find_project_index(store, project).is_none() {
+        add_project(store, project, client, None)?;
+    }
+    let idx = find_project_index(store, project).ok_or("Project could not be created.")?;
+    Ok(Some(store.projects[idx].id.clone()))
+}
+
+struct Columns {
+    headers: Vec<String>,
+}
+
+impl Columns {
+    fn new(headers: Vec<String>) -> Self {
+        Columns { headers }
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.headers.iter().position(|header| header == name)
+    }
+
+    fn require(&self, source: CsvSource) -> Result<(), String> {
+        let required: &[&str] = match source {
+            CsvSource::Toggl | CsvSource::Clockify => &[
+                "description",
+                "start date",
+                "start time",
+                "end date",
+                "end time",
+            ],
+            CsvSource::Harvest => &["date", "hours"],
+        };
+        let missing: Vec<&str> = required
+            .iter()
+            .copied()
+            .filter(|name| self.index(name).is_none())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "CSV is missing expected columns: {}. Is this a detailed time entry export?",
+                missing.join(", ")
+            ))
+        }
+    }
+}
+
+struct Row<'a> {
+    columns: &'a Columns,
+    record: &'a csv::StringRecord,
+}
+
+impl Row<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        let idx = self.columns.index(name)?;
+        let value = self.record.get(idx)?.trim();
+        if value.is_empty() { None } else { Some(value) }
+    }
+}
+
+fn parse_toggl_row(row: &Row, line: usize) -> Result<ImportedEntry, String> {
+    let start_at = parse_local(row.get("start date"), row.get("start time"), "start")?;
+    let end_at = parse_local(row.get("end date"), row.get("end time"), "end")?;
+    build_entry(
+        line,
+        row.get("description").or(row.get("project")),
+        split_tags(row.get("tags")),
+        row.get("project"),
+        row.get("client"),
+        start_at,
+        end_at,
+    )
+}
+
+fn parse_clockify_row(row: &Row, line: usize) -> Result<ImportedEntry, String> {
+    let start_at = parse_local(row.get("start date"), row.get("start time"), "start")?;
+    let end_at = parse_local(row.get("end date"), row.get("end time"), "end")?;
+    let mut tags = split_tags(row.get("tags"));
+    if let Some(task) = row.get("task").and_then(|task| tag_from_label(task).ok())
+        && !tags.contains(&task)
+    {
+        tags.push(task);
+    }
+    build_entry(
+        line,
+        row.get("description")
+            .or(row.get("task"))
+            .or(row.get("project")),
+        tags,
+        row.get("project"),
+        row.get("client"),
+        start_at,
+        end_at,
+    )
+}
+
+fn parse_harvest_row(
+    row: &Row,
+    line: usize,
+    cursor: &mut HashMap<NaiveDate, DateTime<Utc>>,
+) -> Result<ImportedEntry, String> {
+    let date = parse_date(row.get("date").ok_or("missing date")?)?;
+    let hours: f64 = row
+        .get("hours")
+        .ok_or("missing hours")?
+        .parse()
+        .map_err(|_| "invalid hours value".to_string())?;
+    if !hours.is_finite() || hours <= 0.0 {
+        return Err("hours must be positive".into());
+    }
+    let out_of_range = || "hours value out of range".to_string();
+    let duration =
+        Duration::try_seconds((hours * 3600.0).round() as i64).ok_or_else(out_of_range)?;
+
+    let (start_at, end_at) = match (row.get("started at"), row.get("ended at")) {
+        (Some(start), Some(end)) => (
+            parse_local(Some(&date.to_string()), Some(start), "start")?,
+            parse_local(Some(&date.to_string()), Some(end), "end")?,
+        ),
+        _ => {
+            // Harvest exports only carry hours per day, so entries are laid out
+            // back to back from the start of the working day.
+            let day_start = date
+                .and_hms_opt(HARVEST_DAY_START_HOUR, 0, 0)
+                .and_then(local_to_utc)
+                .ok_or("date does not exist in the local timezone")?;
+            let start = *cursor.get(&date).unwrap_or(&day_start);
+            let end = start
+                .checked_add_signed(duration)
+                .ok_or_else(out_of_range)?;
+            cursor.insert(date, end);
+            (start, end)
+        }
+    };
+
+    let mut tags = Vec::new();
+    if let Some(task) = row.get("task").and_then(|task| tag_from_label(task).ok()) {
+        tags.push(task);
+    }
+    build_entry(
+        line,
+        row.get("notes").or(row.get("task")).or(row.get("project")),
+        tags,
+        row.get("project"),
+        row.get("client"),
+        start_at,
+        end_at,
+    )
+}
+
+fn build_entry(
+    line: usize,
+    name: Option<&str>,
+    tags: Vec<String>,
+    project: Option<&str>,
+    client: Option<&str>,
+    start_at: DateTime<Utc>,
+    end_at: DateTime<Utc>,
+) -> Result<ImportedEntry, String> {
+    let name = name.ok_or("missing description")?;
+    if end_at <= start_at {
+        return Err("end is not after start".into());
+    }
+    Ok(ImportedEntry {
+        line,
+        name: name.to_string(),
+        tags,
+        project: project.map(str::to_string),
+        client: client.map(str::to_string),
+        start_at,
+        end_at: Some(end_at),
+    })
+}
+
+fn split_tags(value: Option<&str>) -> Vec<String> {
+    let mut tags = Vec::new();
+    for label in value.unwrap_or_default().split(',') {
+        if let Ok(tag) = tag_from_label(label)
+            && !tags.contains(&tag)
+        {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+pub fn tag_from_label(label: &str) -> Result<String, String> {
+    let joined = label.split_whitespace().collect::<Vec<_>>().join("-");
+    normalize_tag(&joined)
+}
+
+fn parse_local(
+    date: Option<&str>,
+    time: Option<&str>,
+    label: &str,
+) -> Result<DateTime<Utc>, String> {
+    let date = parse_date(date.ok_or_else(|| format!("missing {} date", label))?)?;
+    let time = parse_time(time.ok_or_else(|| format!("missing {} time", label))?)?;
+    local_to_utc(NaiveDateTime::new(date, time))
+        .ok_or_else(|| format!("{} time does not exist in the local timezone", label))
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    ["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%Y", "%Y/%m/%d"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+        .ok_or_else(|| format!("invalid date \"{}\"", value))
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    let compact = value.replace(' ', "");
+    ["%H:%M:%S", "%H:%M", "%I:%M:%S%p", "%I:%M%p"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(&compact, format).ok())
+        .ok_or_else(|| format!("invalid time \"{}\"", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggl_import_skips_bad_rows_and_deduplicates() {
+        let csv = "User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags\n\
+                   Ann,a@x,Acme,Website,,Landing page,Yes,2025-01-06,09:00:00,2025-01-06,10:30:00,01:30:00,\"Design, Code Review\"\n\
+                   Ann,a@x,,,,Broken,No,2025-01-06,nine,2025-01-06,10:00:00,,\n\
+                   Ann,a@x,,,,Overlap,No,2025-01-06,10:00:00,2025-01-06,11:00:00,01:00:00,\n";
+        let (entries, skipped) = parse_csv(CsvSource::Toggl, csv.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].starts_with("line 3:"));
+        assert_eq!(entries[0].tags, vec!["design", "code-review"]);
+
+        let mut store = Store::default();
+        let summary = apply_import(&mut store, entries);
+        assert_eq!(summary.imported, 1);
+        assert_eq!(summary.conflicts.len(), 1);
+        assert_eq!(store.projects[0].name, "Website");
+        assert_eq!(store.clients[0].name, "Acme");
+
+        let (entries, _) = parse_csv(CsvSource::Toggl, csv.as_bytes()).unwrap();
+        let summary = apply_import(&mut store, entries);
+        assert_eq!(summary.imported, 0);
+        assert_eq!(summary.duplicates, 1);
+        assert_eq!(store.tasks.len(), 1);
+    }
+
+    #[test]
+    fn harvest_rows_without_times_are_stacked_per_day() {
+        let csv = "Date,Client,Project,Project Code,Task,Notes,Hours\n\
+                   2025-01-06,Acme,Website,,Design,Wireframes,1.5\n\
+                   2025-01-06,Acme,Website,,Design,,0.5\n";
+        let (entries, skipped) = parse_csv(CsvSource::Harvest, csv.as_bytes()).unwrap();
+        assert!(skipped.is_empty());
+        assert_eq!(entries[0].name, "Wireframes");
+        assert_eq!(entries[1].name, "Design");
+        assert_eq!(entries[0].end_at, Some(entries[1].start_at));
+        assert_eq!(
+            entries[1].end_at.unwrap() - entries[0].start_at,
+            Duration::hours(2)
+        );
+    }
+
+    #[test]
+    fn harvest_rows_with_oversized_hours_are_skipped() {
+        let csv = "Date,Client,Project,Project Code,Task,Notes,Hours\n\
+                   2025-01-06,Acme,Website,,Design,Huge,1e12\n\
+                   2025-01-06,Acme,Website,,Design,Saturated,1e300\n\
+                   2025-01-06,Acme,Website,,Design,Normal,1\n";
+        let (entries, skipped) = parse_csv(CsvSource::Harvest, csv.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "Normal");
+        assert_eq!(
+            skipped,
+            vec![
+                "line 2: hours value out of range",
+                "line 3: hours value out of range"
+            ]
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use crate::export::SegmentRow;
+use crate::import::{ImportedEntry, tag_from_label};
+
+const TIMEWARRIOR_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+const PROJECT_LABEL: &str = "project:";
+const CLIENT_LABEL: &str = "client:";
+
+pub fn parse_timewarrior(text: &str) -> (Vec<ImportedEntry>, Vec<String>) {
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line_number = idx + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match parse_timewarrior_line(line, line_number) {
+            Ok(entry) => entries.push(entry),
+            Err(reason) => skipped.push(format!("line {}: {}", line_number, reason)),
+        }
+    }
+    (entries, skipped)
+}
+
+fn parse_timewarrior_line(line: &str, line_number: usize) -> Result<ImportedEntry, String> {
+    let rest = line
+        .strip_prefix("inc ")
+        .ok_or("not an interval (expected \"inc\")")?;
+    let tokens = tokenize(rest)?;
+    let mut tokens = tokens.into_iter().peekable();
+
+    let start = tokens.next().ok_or("missing start time")?;
+    let start_at = parse_timewarrior_time(&start.0)?;
+    let mut end_at = None;
+    if tokens
+        .peek()
+        .is_some_and(|token| token.0 == "-" && !token.1)
+    {
+        tokens.next();
+        let end = tokens.next().ok_or("missing end time")?;
+        end_at = Some(parse_timewarrior_time(&end.0)?);
+    }
+
+    let mut labels = Vec::new();
+    let mut annotation = Vec::new();
+    match tokens.next() {
+        None => {}
+        Some((marker, false)) if marker == "#" => {
+            let mut in_annotation = false;
+            for (token, quoted) in tokens {
+                if token == "#" && !quoted && !in_annotation {
+                    in_annotation = true;
+                } else if in_annotation {
+                    annotation.push(token);
+                } else {
+                    labels.push(token);
+                }
+            }
+        }
+        Some((token, _)) => return Err(format!("unexpected \"{}\"", token)),
+    }
+
+    if let Some(end_at) = end_at
+        && end_at <= start_at
+    {
+        return Err("end is not after start".into());
+    }
+    let annotation = annotation.join(" ");
+    let name = if !annotation.is_empty() {
+        annotation
+    } else if !labels.is_empty() {
+        labels.join(" ")
+    } else {
+        return Err("interval has no tags or annotation to use as a name".into());
+    };
+    let (tags, project, client) = split_labels(labels.iter().map(String::as_str));
+    Ok(ImportedEntry {
+        line: line_number,
+        name,
+        tags,
+        project,
+        client,
+        start_at,
+        end_at,
+    })
+}
+
+fn parse_timewarrior_time(value: &str) -> Result<DateTime<Utc>, String> {
+    NaiveDateTime::parse_from_str(value, TIMEWARRIOR_FORMAT)
+        .map(|naive| naive.and_utc())
+        .map_err(|_| format!("invalid timestamp \"{}\"", value))
+}
+
+fn tokenize(input: &str) -> Result<Vec<(String, bool)>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&ch) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if ch == '"' {
+            chars.next();
+            let mut token = String::new();
+            loop {
+                match chars.next() {
+                    Some('\\') => token.extend(chars.next()),
+                    Some('"') => break,
+                    Some(other) => token.push(other),
+                    None => return Err("unterminated quote".into()),
+                }
+            }
+            tokens.push((token, true));
+        } else {
+            let mut token = String::new();
+            while let Some(&next) = chars.peek() {
+                if next.is_whitespace() {
+                    break;
+                }
+                token.push(next);
+                chars.next();
+            }
+            tokens.push((token, false));
+        }
+    }
+    Ok(tokens)
+}
+
+pub fn format_timewarrior(rows: &[SegmentRow]) -> String {
+    let mut output = String::new();
+    for row in rows {
+        output.push_str("inc ");
+        output.push_str(&row.start_at.format(TIMEWARRIOR_FORMAT).to_string());
+        if let Some(end_at) = row.end_at {
+            output.push_str(" - ");
+            output.push_str(&end_at.format(TIMEWARRIOR_FORMAT).to_string());
+        }
+        output.push_str(" #");
+        for label in labels(row) {
+            output.push(' ');
+            output.push_str(&quote_timewarrior(&label, false));
+        }
+        output.push_str(" # ");
+        output.push_str(&quote_timewarrior(&row.name, true));
+        output.push('\n');
+    }
+    output
+}
+
+fn quote_timewarrior(value: &str, always: bool) -> String {
+    let needs_quotes = always
+        || value.is_empty()
+        || value
+            .chars()
+            .any(|ch| ch.is_whitespace() || ch == '"' || ch == '#');
+    if !needs_quotes {
+        return value.to_string();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub fn parse_watson(
+    frames: &str,
+    state: Option<&str>,
+) -> Result<(Vec<ImportedEntry>, Vec<String>), String> {
+    let frames: Vec<Value> = serde_json::from_str(frames)
+        .map_err(|err| format!("Invalid Watson frames file: {}", err))?;
+    let mut entries = Vec::new();
+    let mut skipped = Vec::new();
+    for (idx, frame) in frames.iter().enumerate() {
+        let number = idx + 1;
+        let parsed = frame
+            .as_array()
+            .ok_or_else(|| "frame is not an array".to_string())
+            .and_then(|fields| {
+                let start_at = watson_time(fields.first())?;
+                let end_at = watson_time(fields.get(1))?;
+                if end_at <= start_at {
+                    return Err("end is not after start".into());
+                }
+                watson_entry(number, fields.get(2), fields.get(4), start_at, Some(end_at))
+            });
+        match parsed {
+            Ok(entry) => entries.push(entry),
+            Err(reason) => skipped.push(format!("frame {}: {}", number, reason)),
+        }
+    }
+
+    if let Some(state) = state {
+        let state: Value = serde_json::from_str(state)
+            .map_err(|err| format!("Invalid Watson state file: {}", err))?;
+        if state.get("project").is_some() {
+            let parsed = watson_time(state.get("start")).and_then(|start_at| {
+                watson_entry(0, state.get("project"), state.get("tags"), start_at, None)
+            });
+            match parsed {
+                Ok(entry) => entries.push(entry),
+                Err(reason) => skipped.push(format!("state: {}", reason)),
+            }
+        }
+    }
+    Ok((entries, skipped))
+}
+
+fn watson_time(value: Option<&Value>) -> Result<DateTime<Utc>, String> {
+    value
+        .and_then(Value::as_i64)
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .ok_or_else(|| "invalid timestamp".to_string())
+}
+
+fn watson_entry(
+    number: usize,
+    project: Option<&Value>,
+    tags: Option<&Value>,
+    start_at: DateTime<Utc>,
+    end_at: Option<DateTime<Utc>>,
+) -> Result<ImportedEntry, String> {
+    let name = project
+        .and_then(Value::as_str)
+        .filter(|name| !name.trim().is_empty())
+        .ok_or("missing project")?;
+    let (tags, project, client) = split_labels(
+        tags.and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str),
+    );
+    Ok(ImportedEntry {
+        line: number,
+        name: name.to_string(),
+        tags,
+        project,
+        client,
+        start_at,
+        end_at,
+    })
+}
+
+pub fn format_watson_frames(rows: &[SegmentRow], now: DateTime<Utc>) -> Result<String, String> {
+    let frames: Vec<Value> = rows
+        .iter()
+        .filter_map(|row| {
+            let end_at = row.end_at?;
+            Some(json!([
+                row.start_at.timestamp(),
+                end_at.timestamp(),
+                row.name,
+                frame_id(row),
+                labels(row),
+                now.timestamp(),
+            ]))
+        })
+        .collect();
+    serde_json::to_string_pretty(&frames).map_err(|err| err.to_string())
+}
+
+pub fn format_watson_state(rows: &[SegmentRow]) -> Result<String, String> {
+    let state = match rows.iter().find(|row| row.end_at.is_none()) {
+        Some(row) => json!({
+            "project": row.name,
+            "start": row.start_at.timestamp(),
+            "tags": labels(row),
+        }),
+        None => json!({}),
+    };
+    serde_json::to_string_pretty(&state).map_err(|err| err.to_string())
+}
+
+/// Watson frame ids are derived from the task and segment, so exporting twice yields the same
+/// frames and Watson does not see them as new ones.
+fn frame_id(row: &SegmentRow) -> String {
+    let key = format!("{}/{}", row.task_id, row.segment_index);
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, key.as_bytes())
+        .simple()
+        .to_string()
+}
+
+/// Tags plus the project and client link as `project:<name>` and `client:<name>` labels.
+fn labels(row: &SegmentRow) -> Vec<String> {
+    let mut labels = row.tags.clone();
+    labels.extend(
+        row.project
+            .iter()
+            .map(|name| format!("{}{}", PROJECT_LABEL, name)),
+    );
+    labels.extend(
+        row.client
+            .iter()
+            .map(|name| format!("{}{}", CLIENT_LABEL, name)),
+    );
+    labels
+}
+
+fn split_labels<'a>(
+    labels: impl Iterator<Item = &'a str>,
+) -> (Vec<String>, Option<String>, Option<String>) {
+    let mut tags: Vec<String> = Vec::new();
+    let mut project = None;
+    let mut client = None;
+    for label in labels {
+        if let Some(name) = label.strip_prefix(PROJECT_LABEL) {
+            project = Some(name.to_string());
+        } else if let Some(name) = label.strip_prefix(CLIENT_LABEL) {
+            client = Some(name.to_string());
+        } else if let Ok(tag) = tag_from_label(label)
+            && !tags.contains(&tag)
+        {
+            tags.push(tag);
+        }
+    }
+    (tags, project, client)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::import::apply_import;
+    use crate::model::Store;
+    use crate::time::local_date;
+
+    fn row(
+        name: &str,
+        tags: &[&str],
+        start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+    ) -> SegmentRow {
+        SegmentRow {
+            task_id: "id".into(),
+            name: name.into(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            project: None,
+            client: None,
+            start_at: start,
+            end_at: end,
+            seconds: 0,
+            date: local_date(start),
+            segment_index: 0,
+        }
+    }
+
+    #[test]
+    fn timewarrior_roundtrip_keeps_names_tags_and_open_intervals() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2025, 1, 6, 10, 0, 0).unwrap();
+        let rows = vec![
+            row("Write \"docs\"", &["acme", "docs"], start, Some(end)),
+            row("Review", &[], end, None),
+        ];
+        let text = format_timewarrior(&rows);
+        assert!(text.starts_with("inc 20250106T090000Z - 20250106T100000Z # acme docs # "));
+
+        let (entries, skipped) = parse_timewarrior(&text);
+        assert!(skipped.is_empty());
+        assert_eq!(entries[0].name, "Write \"docs\"");
+        assert_eq!(entries[0].tags, vec!["acme", "docs"]);
+        assert_eq!(entries[0].end_at, Some(end));
+        assert_eq!(entries[1].name, "Review");
+        assert_eq!(entries[1].end_at, None);
+
+        let (entries, _) =
+            parse_timewarrior("inc 20250106T090000Z - 20250106T100000Z # \"Client call\" acme\n");
+        assert_eq!(entries[0].name, "Client call acme");
+        assert_eq!(entries[0].tags, vec!["client-call", "acme"]);
+    }
+
+    #[test]
+    fn watson_roundtrip_maps_projects_to_names() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2025, 1, 6, 10, 0, 0).unwrap();
+        let rows = vec![
+            row("website", &["design"], start, Some(end)),
+            row("email", &[], end, None),
+        ];
+        let frames = format_watson_frames(&rows, end).unwrap();
+        let state = format_watson_state(&rows).unwrap();
+
+        let (entries, skipped) = parse_watson(&frames, Some(&state)).unwrap();
+        assert!(skipped.is_empty());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "website");
+        assert_eq!(entries[0].tags, vec!["design"]);
+        assert_eq!(entries[0].start_at, start);
+        assert_eq!(entries[1].name, "email");
+        assert_eq!(entries[1].end_at, None);
+    }
+
+    #[test]
+    fn watson_export_is_stable_and_reimports_as_one_linked_task() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap();
+        let mut rows = Vec::new();
+        for (segment_index, hour) in [9, 11].into_iter().enumerate() {
+            let start = start + chrono::Duration::hours(hour - 9);
+            let mut row = row(
+                "website",
+                &["design"],
+                start,
+                Some(start + chrono::Duration::hours(1)),
+            );
+            row.project = Some("Acme Site".into());
+            row.client = Some("Acme".into());
+            row.segment_index = segment_index;
+            rows.push(row);
+        }
+        let frames = format_watson_frames(&rows, start).unwrap();
+        assert_eq!(frames, format_watson_frames(&rows, start).unwrap());
+        assert!(frames.contains("\"project:Acme Site\""));
+
+        let (entries, _) = parse_watson(&frames, None).unwrap();
+        assert_eq!(entries[0].tags, vec!["design"]);
+        assert_eq!(entries[0].project.as_deref(), Some("Acme Site"));
+        assert_eq!(entries[0].client.as_deref(), Some("Acme"));
+
+        let mut store = Store::default();
+        let summary = apply_import(&mut store, entries);
+        assert_eq!(summary.imported, 2);
+        assert_eq!(store.tasks.len(), 1);
+        assert_eq!(store.tasks[0].segments.len(), 2);
+        assert_eq!(store.tasks[0].closed_at, rows[1].end_at);
+        assert_eq!(store.projects[0].name, "Acme Site");
+    }
+}
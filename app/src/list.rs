@@ -1,18 +1,33 @@
-use chrono::{DateTime, Datelike, Duration, Local, TimeZone, Utc};
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
 
 use crate::model::{Segment, Store};
+use crate::projects::{NO_CLIENT_LABEL, NO_PROJECT_LABEL, task_client_name, task_project_name};
 use crate::report::overlap_window;
 use crate::tasks::task_status;
+use crate::time::{local_date, local_day_start, week_start};
 
+#[derive(Serialize)]
 pub struct TaskListEntry {
     pub name: String,
     pub id: String,
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+    pub client: Option<String>,
     pub status: &'static str,
+    pub archived: bool,
     pub seconds: i64,
     pub start_at: Option<DateTime<Utc>>,
     pub end_at: Option<DateTime<Utc>>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ListGroup {
+    Project,
+    Client,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ListWindow {
     All,
@@ -20,11 +35,30 @@ pub enum ListWindow {
     Week,
 }
 
-pub fn list_tasks(store: &Store, now: DateTime<Utc>, window: ListWindow) -> Vec<TaskListEntry> {
+impl ListWindow {
+    pub fn label(self) -> &'static str {
+        match self {
+            ListWindow::All => "all",
+            ListWindow::Today => "today",
+            ListWindow::Week => "week",
+        }
+    }
+}
+
+pub fn list_tasks(
+    store: &Store,
+    now: DateTime<Utc>,
+    window: ListWindow,
+    tags: &[String],
+    include_archived: bool,
+) -> Vec<TaskListEntry> {
     let bounds = window_bounds(now, window);
     let mut entries = Vec::new();
 
     for task in &store.tasks {
+        if (task.archived && !include_archived) || !tags.iter().all(|tag| task.tags.contains(tag)) {
+            continue;
+        }
         let mut seconds = 0i64;
         let mut earliest: Option<DateTime<Utc>> = None;
         let mut latest: Option<DateTime<Utc>> = None;
@@ -55,7 +89,11 @@ pub fn list_tasks(store: &Store, now: DateTime<Utc>, window: ListWindow) -> Vec<
         entries.push(TaskListEntry {
             name: task.name.clone(),
             id: task.id.clone(),
+            tags: task.tags.clone(),
+            project: task_project_name(store, task),
+            client: task_client_name(store, task),
             status: task_status(task),
+            archived: task.archived,
             seconds,
             start_at: earliest,
             end_at: latest,
@@ -71,18 +109,45 @@ pub fn list_tasks(store: &Store, now: DateTime<Utc>, window: ListWindow) -> Vec<
     entries
 }
 
+pub fn group_list_entries(
+    entries: Vec<TaskListEntry>,
+    group: ListGroup,
+) -> Vec<(String, Vec<TaskListEntry>)> {
+    let mut groups: Vec<(Option<String>, Vec<TaskListEntry>)> = Vec::new();
+    for entry in entries {
+        let key = match group {
+            ListGroup::Project => entry.project.clone(),
+            ListGroup::Client => entry.client.clone(),
+        };
+        match groups.iter_mut().find(|(name, _)| *name == key) {
+            Some((_, members)) => members.push(entry),
+            None => groups.push((key, vec![entry])),
+        }
+    }
+    groups.sort_by(|(a, _), (b, _)| match (a, b) {
+        (Some(a), Some(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    let fallback = match group {
+        ListGroup::Project => NO_PROJECT_LABEL,
+        ListGroup::Client => NO_CLIENT_LABEL,
+    };
+    groups
+        .into_iter()
+        .map(|(name, members)| (name.unwrap_or_else(|| fallback.to_string()), members))
+        .collect()
+}
+
 pub fn list_header(now: DateTime<Utc>, window: ListWindow) -> Option<String> {
     match window {
         ListWindow::All => None,
-        ListWindow::Today => Some(now.with_timezone(&Local).date_naive().to_string()),
+        ListWindow::Today => Some(local_date(now).to_string()),
         ListWindow::Week => {
-            let (start, end) = week_bounds(now);
-            let end_date = end - Duration::days(1);
-            Some(format!(
-                "Week {} to {}",
-                start.date_naive(),
-                end_date.date_naive()
-            ))
+            let start_date = week_start(local_date(now));
+            let end_date = start_date + Duration::days(6);
+            Some(format!("Week {} to {}", start_date, end_date))
         }
     }
 }
@@ -114,41 +179,17 @@ fn segment_bounds(
 }
 
 fn today_bounds(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
-    let now_local = now.with_timezone(&Local);
-    let date = now_local.date_naive();
-    let start_local = date.and_hms_opt(0, 0, 0).unwrap();
-    let end_local = start_local + Duration::days(1);
-
-    let start_utc = Local
-        .from_local_datetime(&start_local)
-        .unwrap()
-        .with_timezone(&Utc);
-    let end_utc = Local
-        .from_local_datetime(&end_local)
-        .unwrap()
-        .with_timezone(&Utc);
-
-    (start_utc, end_utc)
+    let date = local_date(now);
+    (
+        local_day_start(date),
+        local_day_start(date + Duration::days(1)),
+    )
 }
 
 fn week_bounds(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
-    let now_local = now.with_timezone(&Local);
-    let date = now_local.date_naive();
-    let weekday = date.weekday().num_days_from_monday() as i64;
-    let start_date = date - Duration::days(weekday);
-    let end_date = start_date + Duration::days(7);
-
-    let start_local = start_date.and_hms_opt(0, 0, 0).unwrap();
-    let end_local = end_date.and_hms_opt(0, 0, 0).unwrap();
-
-    let start_utc = Local
-        .from_local_datetime(&start_local)
-        .unwrap()
-        .with_timezone(&Utc);
-    let end_utc = Local
-        .from_local_datetime(&end_local)
-        .unwrap()
-        .with_timezone(&Utc);
-
-    (start_utc, end_utc)
+    let start_date = week_start(local_date(now));
+    (
+        local_day_start(start_date),
+        local_day_start(start_date + Duration::days(7)),
+    )
 }
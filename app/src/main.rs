@@ -1,30 +1,76 @@
+mod agent;
 mod cli;
+mod config;
 mod crypto;
+mod doctor;
 mod edit;
+mod export;
+mod import;
+mod interop;
 mod list;
+mod merge;
+mod migrate;
 mod model;
+mod output;
+mod projects;
 mod prompt;
 mod report;
+mod restore;
 mod storage;
 mod tasks;
 mod time;
+mod validate;
 
-use chrono::{DateTime, Local, Utc};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Local, Utc};
 use clap::Parser;
 
-use crate::cli::{Cli, Command};
-use crate::crypto::read_passphrase;
-use crate::edit::{apply_task_edits, edit_task_interactive, resolve_task_index};
-use crate::list::{ListWindow, list_header, list_tasks};
-use crate::model::{Task, TaskState};
+use crate::agent::{AgentClient, serve_from_stdin, spawn_agent};
+use crate::cli::{
+    AgentCommand, Cli, ClientCommand, Command, CsvArgs, EventTimeArgs, KeysCommand, ProjectCommand,
+    RecoveryCommand,
+};
+use crate::config::{BackupConfig, Encryption, load_config};
+use crate::crypto::{
+    KeySlot, Keyfile, PassphraseSources, SlotKind, Unlocker, envelope_slots, is_encrypted,
+    prompt_passphrase, prompt_recovery_code, replace_slots,
+};
+use crate::doctor::{AnomalyKey, apply_fix, find_anomalies};
+use crate::edit::{SegmentOperations, apply_task_edits, edit_task_interactive, resolve_task_index};
+use crate::export::{
+    CsvOptions, parse_delimiter, segment_rows, write_report_csv, write_segments_csv,
+};
+use crate::import::{apply_import, parse_import};
+use crate::interop::{format_timewarrior, format_watson_frames, format_watson_state};
+use crate::list::{ListWindow, TaskListEntry, group_list_entries, list_header, list_tasks};
+use crate::merge::{MergePlan, duplicate_name_plans, merge_tasks};
+use crate::model::{Store, Task, TaskState};
+use crate::output::{
+    ListOutput, OutputFormat, ReportOutput, StatusOutput, StatusTask, print_list, print_report,
+    print_segments, print_status,
+};
+use crate::projects::{
+    add_client, add_project, project_client, resolve_active_project, set_client_archived,
+    set_project_archived, task_project_name,
+};
 use crate::prompt::{prompt_line, prompt_required, prompt_yes_no};
-use crate::report::report_today;
-use crate::storage::{data_file_path, list_backups, load_store, save_store};
+use crate::report::{ReportWindow, day_task_entries, report_days};
+use crate::restore::{StoreDiff, diff_stores, format_signed_duration, restore_tasks};
+use crate::storage::{
+    BackupSummary, DataLock, LOCK_WAIT, Snapshot, data_file_path, list_backups, load_store,
+    lock_data_file, save_payload, save_store,
+};
 use crate::tasks::{
-    active_task_name, current_task_state, pause_task, resume_task, start_task, stop_task,
-    total_elapsed,
+    active_task_name, continue_task, current_task_state, find_task_to_continue, normalize_tag,
+    parse_task_args, pause_task, resume_task, start_task, stop_task, task_status, total_elapsed,
+    validate_close_time, validate_open_time,
+};
+use crate::time::{
+    format_duration, format_time_local_display, local_date, local_day_start, parse_date_input,
+    parse_duration_input, resolve_event_time,
 };
-use crate::time::{format_duration, format_time_local_display};
+use crate::validate::{format_violations, validate_store};
 
 fn main() {
     let cli = Cli::parse();
@@ -32,7 +78,29 @@ fn main() {
 
     let now = Utc::now();
     let command = cli.command;
+    let format = cli.format;
+    let force = cli.force;
+    if format == OutputFormat::Csv
+        && !matches!(&command, Command::Report { .. } | Command::Export { .. })
+    {
+        exit_with_error("CSV output is only available for report and export.");
+    }
+    if matches!(format, OutputFormat::Timewarrior | OutputFormat::Watson)
+        && !matches!(&command, Command::Export { .. })
+    {
+        exit_with_error("Timewarrior and Watson formats are only available for export.");
+    }
 
+    let config = load_config().unwrap_or_else(|err| exit_with_error(&err));
+    let passphrase_sources = PassphraseSources {
+        file: std::env::var_os("TTT_PASSPHRASE_FILE").map(PathBuf::from),
+        fd: cli.passphrase_fd,
+        command: config.passphrase_command.clone(),
+    };
+    let keyfile = cli
+        .keyfile
+        .or_else(|| std::env::var_os("TTT_KEYFILE").map(PathBuf::from))
+        .or_else(|| config.keyfile.clone());
     let data_exists = data_file.exists();
 
     if matches!(&command, Command::Location) {
@@ -43,14 +111,81 @@ fn main() {
         println!("ttt {}", env!("CARGO_PKG_VERSION"));
         return;
     }
-    if matches!(&command, Command::Restore) {
-        let backups = list_backups(&data_file);
+    if let Command::Agent {
+        action,
+        timeout,
+        serve,
+    } = &command
+    {
+        let timeout = parse_duration_input(timeout)
+            .unwrap_or_else(|err| exit_with_error(&err))
+            .to_std()
+            .ok()
+            .filter(|timeout| !timeout.is_zero())
+            .unwrap_or_else(|| exit_with_error("Agent timeout must be a positive duration."));
+        if *serve {
+            serve_from_stdin(&data_file, timeout).unwrap_or_else(|err| exit_with_error(&err));
+            return;
+        }
+        let running = AgentClient::find(&data_file)
+            .and_then(|client| client.status().ok().map(|locks_in| (client, locks_in)));
+        match (action, running) {
+            (Some(AgentCommand::Status), Some((_, locks_in))) => println!(
+                "Agent running for {}; it forgets the key in {}.",
+                data_file.display(),
+                format_duration(locks_in.as_secs() as i64)
+            ),
+            (Some(AgentCommand::Lock), Some((client, _))) => {
+                client.lock().unwrap_or_else(|err| exit_with_error(&err));
+                println!("Agent locked for {}.", data_file.display());
+            }
+            (Some(_), None) => println!("No agent is running for {}.", data_file.display()),
+            (None, Some(_)) => exit_with_error(&format!(
+                "An agent is already running for {}. Stop it with \"ttt agent lock\".",
+                data_file.display()
+            )),
+            (None, None) => {
+                if !data_exists {
+                    exit_with_error("No data file found. Start tracking with \"ttt start\" first.");
+                }
+                let payload = std::fs::read_to_string(&data_file)
+                    .unwrap_or_else(|err| exit_with_error(&err.to_string()));
+                ensure_encrypted(&data_file, &payload);
+                let unlocker = Unlocker::new(passphrase_sources, keyfile, false, None);
+                unlocker
+                    .open(&payload)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                let key = unlocker
+                    .into_key()
+                    .expect("opening the data file unlocks its key");
+                spawn_agent(&data_file, &key, timeout).unwrap_or_else(|err| exit_with_error(&err));
+                println!(
+                    "Agent started for {}; it forgets the key in {}. Run \"ttt agent lock\" to forget it sooner.",
+                    data_file.display(),
+                    format_duration(timeout.as_secs() as i64)
+                );
+            }
+        }
+        return;
+    }
+    if let Command::Restore { tasks } = &command {
+        let backups = list_backups(&data_file, &config.backup);
         if backups.is_empty() {
             exit_with_error("No backups found.");
         }
+        let unlocker = Unlocker::new(
+            passphrase_sources,
+            keyfile,
+            false,
+            AgentClient::find(&data_file),
+        );
+        unlocker
+            .prepare(&data_file)
+            .unwrap_or_else(|err| exit_with_error(&err));
         println!("Available backups:");
         for (idx, entry) in backups.iter().enumerate() {
-            println!("{:>3}) {}", idx + 1, format_backup_entry(entry));
+            let summary = load_store(&entry.path, &unlocker).map(|store| BackupSummary::of(&store));
+            println!("{:>3}) {}", idx + 1, format_backup_entry(entry, &summary));
         }
         let input = prompt_line("Select backup number (or 'q' to cancel): ")
             .unwrap_or_else(|err| exit_with_error(&err));
@@ -69,124 +204,532 @@ fn main() {
             ));
         }
         let entry = &backups[selection - 1];
-        let label = format_backup_entry(entry);
-        if !prompt_yes_no(&format!("Restore {}? [y/N] ", label)) {
-            exit_with_error("Canceled.");
-        }
-        let passphrase = read_passphrase(false).unwrap_or_else(|err| exit_with_error(&err));
-        let store = match load_store(&entry.path, &passphrase) {
+        let backup = match load_store(&entry.path, &unlocker) {
             Ok(store) => store,
             Err(err) => exit_with_error(&err),
         };
-        save_store(&data_file, &store, &passphrase).unwrap_or_else(|err| exit_with_error(&err));
-        println!("Restored backup {}", entry.path.display());
+
+        let _lock =
+            lock_data_file(&data_file, LOCK_WAIT).unwrap_or_else(|err| exit_with_error(&err));
+        let snapshot = Snapshot::take(&data_file).unwrap_or_else(|err| exit_with_error(&err));
+        let current = load_store(&data_file, &unlocker);
+        let restored = if tasks.is_empty() {
+            backup
+        } else {
+            let mut restored = current.clone().unwrap_or_else(|err| exit_with_error(&err));
+            restore_tasks(&mut restored, backup, tasks).unwrap_or_else(|err| exit_with_error(&err));
+            restored
+        };
+        match &current {
+            Ok(current) => print_restore_diff(&diff_stores(current, &restored, now)),
+            Err(err) => println!(
+                "The current data file cannot be read ({}); no comparison is available.",
+                err
+            ),
+        }
+        let question = if tasks.is_empty() {
+            format!("Replace {} with this backup? [y/N] ", data_file.display())
+        } else {
+            format!("Restore {} task(s) from this backup? [y/N] ", tasks.len())
+        };
+        if !prompt_yes_no(&question) {
+            exit_with_error("Canceled.");
+        }
+        if tasks.is_empty() {
+            snapshot
+                .ensure_unchanged(&data_file)
+                .and_then(|()| save_store(&data_file, &restored, &unlocker, &config.backup))
+                .unwrap_or_else(|err| exit_with_error(&err));
+            println!("Restored backup {}", entry.path.display());
+        } else {
+            save_checked(
+                &data_file,
+                &restored,
+                &unlocker,
+                force,
+                &snapshot,
+                &config.backup,
+            )
+            .unwrap_or_else(|err| exit_with_error(&err));
+            println!(
+                "Restored {} task(s) from {}",
+                tasks.len(),
+                entry.path.display()
+            );
+        }
         return;
     }
     if matches!(&command, Command::Rekey) {
         if !data_exists {
             exit_with_error("No data file found. Start tracking with \"ttt start\" first.");
         }
-        let current_passphrase = read_passphrase(false).unwrap_or_else(|err| exit_with_error(&err));
-        let store = match load_store(&data_file, &current_passphrase) {
-            Ok(store) => store,
-            Err(err) => exit_with_error(&err),
-        };
-        let new_passphrase = read_passphrase(true).unwrap_or_else(|err| exit_with_error(&err));
-        save_store(&data_file, &store, &new_passphrase).unwrap_or_else(|err| exit_with_error(&err));
+        let unlocker = Unlocker::new(passphrase_sources, keyfile, false, None);
+        let (_lock, snapshot, payload) = unlock_for_key_change(&data_file, &unlocker);
+        let new_passphrase = prompt_passphrase(true).unwrap_or_else(|err| exit_with_error(&err));
+        let new_slot = unlocker
+            .with_key(|key| key.wrap_passphrase(&new_passphrase))
+            .unwrap_or_else(|err| exit_with_error(&err));
+        let mut slots = unlocker.slots();
+        slots.retain(|slot| slot.kind != SlotKind::Passphrase);
+        slots.push(new_slot);
+        snapshot
+            .ensure_unchanged(&data_file)
+            .and_then(|()| replace_slots(&payload, slots))
+            .and_then(|updated| save_payload(&data_file, &updated, &config.backup))
+            .unwrap_or_else(|err| exit_with_error(&err));
         println!("Passphrase updated for {}", data_file.display());
         return;
     }
+    if matches!(&command, Command::Encrypt | Command::Decrypt) {
+        if !data_exists {
+            exit_with_error("No data file found. Start tracking with \"ttt start\" first.");
+        }
+        let encrypt = matches!(&command, Command::Encrypt);
+        let payload = std::fs::read_to_string(&data_file)
+            .unwrap_or_else(|err| exit_with_error(&err.to_string()));
+        if is_encrypted(&payload) == encrypt {
+            exit_with_error(&format!(
+                "{} is {}.",
+                data_file.display(),
+                if encrypt {
+                    "already encrypted"
+                } else {
+                    "not encrypted"
+                }
+            ));
+        }
+        let plain = || {
+            Unlocker::new(PassphraseSources::default(), None, false, None)
+                .with_encryption(Encryption::None)
+        };
+        let (current, target) = if encrypt {
+            println!(
+                "This encrypts {} with a new passphrase. Without the passphrase or a recovery code, the data cannot be recovered.",
+                data_file.display()
+            );
+            println!(
+                "The plain file is kept as a backup; delete plain backups yourself if they must not stay readable."
+            );
+            (plain(), Unlocker::new(passphrase_sources, None, true, None))
+        } else {
+            println!(
+                "WARNING: this writes your time data to {} as plain JSON. Anyone who can read that file, its backups or your disk can read your data.",
+                data_file.display()
+            );
+            println!("The encrypted file is kept as a backup.");
+            let current = Unlocker::new(
+                passphrase_sources,
+                keyfile,
+                false,
+                AgentClient::find(&data_file),
+            );
+            (current, plain())
+        };
+        let question = format!(
+            "{} {}? [y/N] ",
+            if encrypt { "Encrypt" } else { "Decrypt" },
+            data_file.display()
+        );
+        if !prompt_yes_no(&question) {
+            exit_with_error("Canceled.");
+        }
+        current
+            .prepare(&data_file)
+            .and_then(|()| {
+                if encrypt {
+                    target.passphrase().map(|_| ())
+                } else {
+                    Ok(())
+                }
+            })
+            .unwrap_or_else(|err| exit_with_error(&err));
+        let _lock =
+            lock_data_file(&data_file, LOCK_WAIT).unwrap_or_else(|err| exit_with_error(&err));
+        let snapshot = Snapshot::take(&data_file).unwrap_or_else(|err| exit_with_error(&err));
+        let store = load_store(&data_file, &current).unwrap_or_else(|err| exit_with_error(&err));
+        snapshot
+            .ensure_unchanged(&data_file)
+            .and_then(|()| save_store(&data_file, &store, &target, &config.backup))
+            .unwrap_or_else(|err| exit_with_error(&err));
+        if encrypt {
+            println!("Encrypted {}", data_file.display());
+            print_recovery_codes(&data_file, &target.take_recovery_codes());
+        } else {
+            println!("Decrypted {}; it is now plain JSON.", data_file.display());
+            if let Some(client) = AgentClient::find(&data_file)
+                && client.lock().is_ok()
+            {
+                println!("Locked the agent; a plain data file needs none.");
+            }
+        }
+        return;
+    }
+    if let Command::Recovery { action } = &command {
+        if !data_exists {
+            exit_with_error("No data file found. Start tracking with \"ttt start\" first.");
+        }
+        match action {
+            RecoveryCommand::Generate { count } => {
+                if *count == 0 {
+                    exit_with_error("--count must be at least 1.");
+                }
+                let unlocker = Unlocker::new(passphrase_sources, keyfile, false, None);
+                let (_lock, snapshot, payload) = unlock_for_key_change(&data_file, &unlocker);
+                let replaced = unlocker
+                    .slots()
+                    .iter()
+                    .filter(|slot| slot.kind == SlotKind::Recovery)
+                    .count();
+                let (rotated, dropped) = unlocker
+                    .rotate_key(&payload, None, *count)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                snapshot
+                    .ensure_unchanged(&data_file)
+                    .and_then(|()| save_payload(&data_file, &rotated, &config.backup))
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                print_recovery_codes(&data_file, &unlocker.take_recovery_codes());
+                if replaced > 0 {
+                    println!(
+                        "The data key was replaced, so the {} previous recovery code(s) no longer open {}. Backups made before keep them.",
+                        replaced,
+                        data_file.display()
+                    );
+                }
+                finish_key_rotation(&data_file, dropped);
+            }
+            RecoveryCommand::Use => {
+                let unlocker = Unlocker::new(passphrase_sources, keyfile, false, None);
+                let _lock = lock_data_file(&data_file, LOCK_WAIT)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                let snapshot =
+                    Snapshot::take(&data_file).unwrap_or_else(|err| exit_with_error(&err));
+                let payload = std::fs::read_to_string(&data_file)
+                    .map_err(|err| format!("Could not read {}: {}", data_file.display(), err))
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                ensure_encrypted(&data_file, &payload);
+                let codes = envelope_slots(&payload)
+                    .unwrap_or_else(|err| exit_with_error(&err))
+                    .iter()
+                    .filter(|slot| slot.kind == SlotKind::Recovery)
+                    .count();
+                if codes == 0 {
+                    exit_with_error(&format!("{} has no recovery codes.", data_file.display()));
+                }
+                let code = prompt_recovery_code().unwrap_or_else(|err| exit_with_error(&err));
+                unlocker
+                    .open_with_recovery_code(&payload, &code)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                println!("Recovery code accepted. Choose a new passphrase.");
+                let new_passphrase =
+                    prompt_passphrase(true).unwrap_or_else(|err| exit_with_error(&err));
+                // The other codes cannot be wrapped again without knowing them, so the same
+                // number of new ones replaces them.
+                let (rotated, dropped) = unlocker
+                    .rotate_key(&payload, Some(&new_passphrase), codes - 1)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                snapshot
+                    .ensure_unchanged(&data_file)
+                    .and_then(|()| save_payload(&data_file, &rotated, &config.backup))
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                println!(
+                    "Passphrase reset for {}. The data key was replaced, so old recovery codes, including the one just used, no longer open it; backups made before still accept them.",
+                    data_file.display()
+                );
+                let new_codes = unlocker.take_recovery_codes();
+                if new_codes.is_empty() {
+                    println!(
+                        "No recovery codes are left; run \"ttt recovery generate\" for a new set."
+                    );
+                }
+                print_recovery_codes(&data_file, &new_codes);
+                finish_key_rotation(&data_file, dropped);
+            }
+        }
+        return;
+    }
+    if let Command::Keys { action } = &command {
+        if !data_exists {
+            exit_with_error("No data file found. Start tracking with \"ttt start\" first.");
+        }
+        let unlocker = Unlocker::new(passphrase_sources, keyfile, false, None);
+        match action.as_ref().unwrap_or(&KeysCommand::List) {
+            KeysCommand::List => {
+                let payload = std::fs::read_to_string(&data_file)
+                    .unwrap_or_else(|err| exit_with_error(&err.to_string()));
+                ensure_encrypted(&data_file, &payload);
+                let slots = envelope_slots(&payload).unwrap_or_else(|err| exit_with_error(&err));
+                println!("Unlock methods for {}:", data_file.display());
+                if slots.is_empty() {
+                    println!("  passphrase (older format; the next save adds numbered methods)");
+                }
+                for (idx, slot) in slots.iter().enumerate() {
+                    println!(
+                        "{:>3}) {:<10} added {}",
+                        idx + 1,
+                        slot.kind.label(),
+                        slot.created_at
+                            .with_timezone(&Local)
+                            .format("%Y-%m-%d %H:%M")
+                    );
+                }
+            }
+            KeysCommand::AddKeyfile { path } => {
+                change_key_slots(&data_file, &unlocker, &config.backup, |slots| {
+                    let created = !path.exists();
+                    let new_keyfile = if created {
+                        Keyfile::create(path)
+                    } else {
+                        Keyfile::read(path)
+                    }
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                    if slots.iter().any(|slot| slot.matches_keyfile(&new_keyfile)) {
+                        exit_with_error(&format!(
+                            "{} already unlocks {}.",
+                            path.display(),
+                            data_file.display()
+                        ));
+                    }
+                    let slot = unlocker
+                        .with_key(|key| key.wrap_keyfile(&new_keyfile))
+                        .unwrap_or_else(|err| exit_with_error(&err));
+                    slots.push(slot);
+                    let mut message = format!(
+                        "Added keyfile {} as unlock method {}. Use it with --keyfile, TTT_KEYFILE or \"keyfile\" in the config file.",
+                        path.display(),
+                        slots.len()
+                    );
+                    if created {
+                        message.push_str(&format!(
+                            "\nCreated {}. Keep a copy somewhere safe: it unlocks your data without the passphrase.",
+                            path.display()
+                        ));
+                    }
+                    message
+                });
+            }
+            KeysCommand::Remove { number } => {
+                change_key_slots(&data_file, &unlocker, &config.backup, |slots| {
+                    if *number == 0 || *number > slots.len() {
+                        exit_with_error(&format!(
+                            "Unlock method must be between 1 and {}; see \"ttt keys\".",
+                            slots.len()
+                        ));
+                    }
+                    if slots.len() == 1 {
+                        exit_with_error("Cannot remove the only way to unlock the data file.");
+                    }
+                    let kind = slots[number - 1].kind.label();
+                    if !prompt_yes_no(&format!(
+                        "Remove unlock method {} ({})? [y/N] ",
+                        number, kind
+                    )) {
+                        exit_with_error("Canceled.");
+                    }
+                    slots.remove(number - 1);
+                    format!("Removed unlock method {} ({}).", number, kind)
+                });
+            }
+        }
+        return;
+    }
 
     let will_write = matches!(
         &command,
         Command::Start { .. }
-            | Command::Stop
-            | Command::Pause
-            | Command::Resume
+            | Command::Continue { .. }
+            | Command::Stop { .. }
+            | Command::Pause { .. }
+            | Command::Resume { .. }
             | Command::Edit { .. }
+            | Command::Delete { .. }
+            | Command::Archive { .. }
+            | Command::Unarchive { .. }
+            | Command::Merge { .. }
+            | Command::Project { .. }
+            | Command::Client { .. }
+            | Command::Import { .. }
     );
     let is_new_store = !data_exists;
     let confirm_passphrase = will_write && is_new_store;
-    let passphrase =
-        read_passphrase(confirm_passphrase).unwrap_or_else(|err| exit_with_error(&err));
-    let mut store = match load_store(&data_file, &passphrase) {
+    let unlocker = Unlocker::new(
+        passphrase_sources,
+        keyfile,
+        confirm_passphrase,
+        AgentClient::find(&data_file),
+    )
+    .with_encryption(config.encryption);
+    unlocker
+        .prepare(&data_file)
+        .unwrap_or_else(|err| exit_with_error(&err));
+    // Hold the lock from load to save so concurrent commands cannot overwrite each other.
+    let _lock = (will_write || matches!(&command, Command::Doctor { .. }))
+        .then(|| lock_data_file(&data_file, LOCK_WAIT).unwrap_or_else(|err| exit_with_error(&err)));
+    let snapshot = Snapshot::take(&data_file).unwrap_or_else(|err| exit_with_error(&err));
+    let mut store = match load_store(&data_file, &unlocker) {
         Ok(store) => store,
         Err(err) => exit_with_error(&err),
     };
 
     match command {
-        Command::Start { task } => {
-            let task_name = match task {
-                Some(name) if !name.trim().is_empty() => name,
-                Some(_) => exit_with_error("Task name cannot be empty."),
-                None => prompt_required("Task name: ", "Task name")
-                    .unwrap_or_else(|err| exit_with_error(&err)),
-            };
-            if let Some((idx, state)) = current_task_state(&store) {
-                let existing_name = store.tasks[idx].name.clone();
-                let prompt = match state {
-                    TaskState::Active => format!(
-                        "Active task \"{}\" is running. Stop it and start \"{}\"? [y/N] ",
-                        existing_name, task_name
-                    ),
-                    TaskState::Paused => format!(
-                        "Task \"{}\" is paused. Abandon it and start \"{}\"? [y/N] ",
-                        existing_name, task_name
-                    ),
-                };
-                if !prompt_yes_no(&prompt) {
-                    exit_with_error("Canceled.");
+        Command::Start {
+            task,
+            continue_task: true,
+            when,
+            ..
+        } => {
+            let (task_name, tags) =
+                parse_task_args(&task).unwrap_or_else(|err| exit_with_error(&err));
+            let at = event_time(&when, now);
+            let query = (!task_name.is_empty()).then_some(task_name.as_str());
+            match find_task_to_continue(&store, query) {
+                Ok(idx) => {
+                    for tag in tags {
+                        if !store.tasks[idx].tags.contains(&tag) {
+                            store.tasks[idx].tags.push(tag);
+                        }
+                    }
+                    continue_existing(&mut store, idx, at);
+                    save_checked(
+                        &data_file,
+                        &store,
+                        &unlocker,
+                        force,
+                        &snapshot,
+                        &config.backup,
+                    )
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                    print_continued(&store.tasks[idx], at, now);
+                }
+                Err(err) if query.is_none() => exit_with_error(&err),
+                Err(_) => {
+                    start_new(&mut store, task_name.clone(), tags.clone(), None, at);
+                    save_checked(
+                        &data_file,
+                        &store,
+                        &unlocker,
+                        force,
+                        &snapshot,
+                        &config.backup,
+                    )
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                    println!(
+                        "Started: {}{} at {}",
+                        task_name,
+                        format_tags(&tags),
+                        format_time_local_display(at)
+                    );
+                }
+            }
+        }
+        Command::Continue { task, when } => {
+            let idx = match task.as_deref() {
+                Some(query) if query.parse::<usize>().is_ok() => {
+                    resolve_task_index(&store, now, None, query.parse().ok(), "continue", false)
                 }
-                stop_task(&mut store, idx, now);
+                query => find_task_to_continue(&store, query),
             }
-            start_task(&mut store, task_name.clone(), now);
-            save_store(&data_file, &store, &passphrase).unwrap_or_else(|err| exit_with_error(&err));
+            .unwrap_or_else(|err| exit_with_error(&err));
+            let at = event_time(&when, now);
+            continue_existing(&mut store, idx, at);
+            save_checked(
+                &data_file,
+                &store,
+                &unlocker,
+                force,
+                &snapshot,
+                &config.backup,
+            )
+            .unwrap_or_else(|err| exit_with_error(&err));
+            print_continued(&store.tasks[idx], at, now);
+        }
+        Command::Start {
+            task,
+            project,
+            when,
+            ..
+        } => {
+            let (task_name, tags) =
+                parse_task_args(&task).unwrap_or_else(|err| exit_with_error(&err));
+            let task_name = if !task_name.is_empty() {
+                task_name
+            } else if !task.is_empty() && tags.is_empty() {
+                exit_with_error("Task name cannot be empty.")
+            } else {
+                prompt_required("Task name: ", "Task name")
+                    .unwrap_or_else(|err| exit_with_error(&err))
+            };
+            let project_id = project.map(|name| {
+                resolve_active_project(&store, &name).unwrap_or_else(|err| exit_with_error(&err))
+            });
+            let at = event_time(&when, now);
+            start_new(&mut store, task_name.clone(), tags.clone(), project_id, at);
+            save_checked(
+                &data_file,
+                &store,
+                &unlocker,
+                force,
+                &snapshot,
+                &config.backup,
+            )
+            .unwrap_or_else(|err| exit_with_error(&err));
             println!(
-                "Started: {} at {}",
+                "Started: {}{} at {}",
                 task_name,
-                format_time_local_display(now)
+                format_tags(&tags),
+                format_time_local_display(at)
             );
-            if is_new_store {
-                println!("Created encrypted data file at {}", data_file.display());
-            }
         }
-        Command::Stop => {
+        Command::Stop { when } => {
             if let Some((idx, _)) = current_task_state(&store) {
+                let at = event_time(&when, now);
+                validate_close_time(&store.tasks[idx], at)
+                    .unwrap_or_else(|err| exit_with_error(&err));
                 let task_name = store.tasks[idx].name.clone();
-                stop_task(&mut store, idx, now);
+                stop_task(&mut store, idx, at);
                 let elapsed = total_elapsed(&store.tasks[idx], now);
-                save_store(&data_file, &store, &passphrase)
-                    .unwrap_or_else(|err| exit_with_error(&err));
+                save_checked(
+                    &data_file,
+                    &store,
+                    &unlocker,
+                    force,
+                    &snapshot,
+                    &config.backup,
+                )
+                .unwrap_or_else(|err| exit_with_error(&err));
                 println!(
                     "Stopped: {} at {} (total {})",
                     task_name,
-                    format_time_local_display(now),
+                    format_time_local_display(at),
                     format_duration(elapsed)
                 );
-                if is_new_store {
-                    println!("Created encrypted data file at {}", data_file.display());
-                }
             } else {
                 exit_with_error("No active or paused task. Start one with \"ttt start <task>\".");
             }
         }
-        Command::Pause => {
+        Command::Pause { when } => {
             if let Some((idx, state)) = current_task_state(&store) {
                 if state == TaskState::Active {
+                    let at = event_time(&when, now);
+                    validate_close_time(&store.tasks[idx], at)
+                        .unwrap_or_else(|err| exit_with_error(&err));
                     let task_name = store.tasks[idx].name.clone();
-                    pause_task(&mut store, idx, now);
+                    pause_task(&mut store, idx, at);
                     let elapsed = total_elapsed(&store.tasks[idx], now);
-                    save_store(&data_file, &store, &passphrase)
-                        .unwrap_or_else(|err| exit_with_error(&err));
+                    save_checked(
+                        &data_file,
+                        &store,
+                        &unlocker,
+                        force,
+                        &snapshot,
+                        &config.backup,
+                    )
+                    .unwrap_or_else(|err| exit_with_error(&err));
                     println!(
                         "Paused: {} at {} (total {})",
                         task_name,
-                        format_time_local_display(now),
+                        format_time_local_display(at),
                         format_duration(elapsed)
                     );
-                    if is_new_store {
-                        println!("Created encrypted data file at {}", data_file.display());
-                    }
                 } else {
                     exit_with_error("Task is already paused. Resume it with \"ttt resume\".");
                 }
@@ -194,20 +737,27 @@ fn main() {
                 exit_with_error("No active task. Start one with \"ttt start <task>\".");
             }
         }
-        Command::Resume => match current_task_state(&store) {
+        Command::Resume { when } => match current_task_state(&store) {
             Some((idx, TaskState::Paused)) => {
-                let task_name = store.tasks[idx].name.clone();
-                resume_task(&mut store, idx, now);
-                save_store(&data_file, &store, &passphrase)
+                let at = event_time(&when, now);
+                validate_open_time(&store, Some(idx), at)
                     .unwrap_or_else(|err| exit_with_error(&err));
+                let task_name = store.tasks[idx].name.clone();
+                resume_task(&mut store, idx, at);
+                save_checked(
+                    &data_file,
+                    &store,
+                    &unlocker,
+                    force,
+                    &snapshot,
+                    &config.backup,
+                )
+                .unwrap_or_else(|err| exit_with_error(&err));
                 println!(
                     "Resumed: {} at {}",
                     task_name,
-                    format_time_local_display(now)
+                    format_time_local_display(at)
                 );
-                if is_new_store {
-                    println!("Created encrypted data file at {}", data_file.display());
-                }
             }
             Some((_, TaskState::Active)) => {
                 let active_name = active_task_name(&store).unwrap_or_default();
@@ -220,6 +770,35 @@ fn main() {
                 exit_with_error("No paused task. Start one with \"ttt start <task>\".");
             }
         },
+        Command::Status if format != OutputFormat::Text => {
+            let output = match current_task_state(&store) {
+                Some((idx, state)) => {
+                    let task = &store.tasks[idx];
+                    let since = match state {
+                        TaskState::Active => active_segment_start(task),
+                        TaskState::Paused => last_segment_end(task),
+                    };
+                    StatusOutput {
+                        state: Some(state),
+                        task: Some(StatusTask {
+                            id: task.id.clone(),
+                            name: task.name.clone(),
+                            tags: task.tags.clone(),
+                            project: task_project_name(&store, task),
+                        }),
+                        elapsed_seconds: total_elapsed(task, now),
+                        since: Some(since.unwrap_or(task.created_at)),
+                    }
+                }
+                None => StatusOutput {
+                    state: None,
+                    task: None,
+                    elapsed_seconds: 0,
+                    since: None,
+                },
+            };
+            print_status(format, &output).unwrap_or_else(|err| exit_with_error(&err));
+        }
         Command::Status => match current_task_state(&store) {
             Some((idx, TaskState::Active)) => {
                 let task = &store.tasks[idx];
@@ -245,7 +824,13 @@ fn main() {
             }
             None => println!("No active task. Start one with \"ttt start\"."),
         },
-        Command::List { today, week } => {
+        Command::List {
+            today,
+            week,
+            tags,
+            group,
+            archived,
+        } => {
             if today && week {
                 exit_with_error("Use either --today or --week, not both.");
             }
@@ -256,7 +841,22 @@ fn main() {
             } else {
                 ListWindow::All
             };
-            let entries = list_tasks(&store, now, window);
+            let tags: Vec<String> = tags
+                .iter()
+                .map(|tag| normalize_tag(tag))
+                .collect::<Result<_, _>>()
+                .unwrap_or_else(|err| exit_with_error(&err));
+            let entries = list_tasks(&store, now, window, &tags, archived);
+            let total_seconds: i64 = entries.iter().map(|entry| entry.seconds).sum();
+            if format != OutputFormat::Text {
+                let output = ListOutput {
+                    window: window.label(),
+                    total_seconds,
+                    tasks: &entries,
+                };
+                print_list(format, &output).unwrap_or_else(|err| exit_with_error(&err));
+                return;
+            }
             if entries.is_empty() {
                 println!("No matching tasks.");
                 return;
@@ -264,73 +864,585 @@ fn main() {
             if let Some(header) = list_header(now, window) {
                 println!("{}", header);
             }
-            let total_seconds: i64 = entries.iter().map(|entry| entry.seconds).sum();
-            for (idx, entry) in entries.iter().enumerate() {
-                println!(
-                    "{:>3}) [{}] {} ({}) total {}",
-                    idx + 1,
-                    entry.status,
-                    entry.name,
-                    entry.id,
-                    format_duration(entry.seconds)
-                );
+            match group {
+                None => {
+                    for (idx, entry) in entries.iter().enumerate() {
+                        print_list_entry(idx + 1, entry);
+                    }
+                }
+                Some(group) => {
+                    let mut position = 0;
+                    for (name, members) in group_list_entries(entries, group) {
+                        println!("{}", name);
+                        for entry in &members {
+                            position += 1;
+                            print_list_entry(position, entry);
+                        }
+                        let subtotal: i64 = members.iter().map(|entry| entry.seconds).sum();
+                        println!("     Subtotal: {}", format_duration(subtotal));
+                    }
+                }
             }
             println!("Total: {}", format_duration(total_seconds));
         }
-        Command::Report { today: _ } => {
-            let report = report_today(&store, now);
-            if report.is_empty() {
-                println!("No entries for today.");
+        Command::Report {
+            today,
+            yesterday,
+            week,
+            month,
+            from,
+            to,
+            group,
+            csv,
+        } => {
+            let window = resolve_report_window(today, yesterday, week, month, from, to, now)
+                .unwrap_or_else(|err| exit_with_error(&err));
+            let (first_day, last_day) = window.dates(now);
+            let days = report_days(&store, first_day, last_day, now, group);
+            if format == OutputFormat::Csv {
+                let options = csv_options(&csv).unwrap_or_else(|err| exit_with_error(&err));
+                write_report_csv(std::io::stdout().lock(), &days, options)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                return;
+            }
+            if format != OutputFormat::Text {
+                let task_entries: Vec<_> = days
+                    .iter()
+                    .flat_map(|day| day_task_entries(&store, day.date, now))
+                    .collect();
+                let output = ReportOutput {
+                    from: first_day,
+                    to: last_day,
+                    group,
+                    total_seconds: task_entries.iter().map(|entry| entry.seconds).sum(),
+                    total_amount: sum_amounts(task_entries.iter().map(|entry| entry.amount)),
+                    days: &days,
+                };
+                print_report(format, &output).unwrap_or_else(|err| exit_with_error(&err));
+                return;
+            }
+            if days.is_empty() {
+                if window == ReportWindow::Today {
+                    println!("No entries for today.");
+                } else if first_day == last_day {
+                    println!("No entries for {}.", first_day);
+                } else {
+                    println!("No entries from {} to {}.", first_day, last_day);
+                }
                 return;
             }
-            let report_date = now.with_timezone(&Local).date_naive();
-            println!("{}", report_date);
-            let total_seconds: i64 = report.iter().map(|entry| entry.seconds).sum();
-            for entry in report {
+            let mut grand_total = 0i64;
+            let mut grand_amount = None;
+            for (idx, day) in days.iter().enumerate() {
+                if idx > 0 {
+                    println!();
+                }
+                println!("{}", day.date);
+                let task_entries = day_task_entries(&store, day.date, now);
+                let total_seconds: i64 = task_entries.iter().map(|entry| entry.seconds).sum();
+                let total_amount = sum_amounts(task_entries.iter().map(|entry| entry.amount));
+                for entry in &day.entries {
+                    println!(
+                        "{} - {} - {} ({}{})",
+                        format_time_local_display(entry.start_at),
+                        format_time_local_display(entry.end_at),
+                        entry.name,
+                        format_duration(entry.seconds),
+                        format_amount(entry.amount)
+                    );
+                }
                 println!(
-                    "{} - {} - {} ({})",
-                    format_time_local_display(entry.start_at),
-                    format_time_local_display(entry.end_at),
-                    entry.name,
-                    format_duration(entry.seconds)
+                    "Total: {}{}",
+                    format_duration(total_seconds),
+                    format_total_amount(total_amount)
                 );
+                grand_total += total_seconds;
+                grand_amount = sum_amounts([grand_amount, total_amount]);
+            }
+            if first_day != last_day {
+                println!();
+                println!(
+                    "Grand total ({} to {}): {}{}",
+                    first_day,
+                    last_day,
+                    format_duration(grand_total),
+                    format_total_amount(grand_amount)
+                );
+            }
+        }
+        Command::Export {
+            from,
+            to,
+            output,
+            csv,
+        } => {
+            if output.is_some() && format != OutputFormat::Watson {
+                exit_with_error("--output is only supported with --format watson.");
+            }
+            let from = from.map(|value| {
+                parse_date_input(&value, "--from").unwrap_or_else(|err| exit_with_error(&err))
+            });
+            let to = to.map(|value| {
+                parse_date_input(&value, "--to").unwrap_or_else(|err| exit_with_error(&err))
+            });
+            let window = match (from, to) {
+                (None, None) => None,
+                (from, to) => Some((
+                    from.map(local_day_start)
+                        .unwrap_or(DateTime::<Utc>::MIN_UTC),
+                    to.map(|date| local_day_start(date + Duration::days(1)))
+                        .unwrap_or(DateTime::<Utc>::MAX_UTC),
+                )),
+            };
+            let rows = segment_rows(&store, window, now);
+            match format {
+                OutputFormat::Json | OutputFormat::Ndjson => {
+                    print_segments(format, &rows).unwrap_or_else(|err| exit_with_error(&err));
+                }
+                OutputFormat::Timewarrior => print!("{}", format_timewarrior(&rows)),
+                OutputFormat::Watson => {
+                    let frames = format_watson_frames(&rows, now)
+                        .unwrap_or_else(|err| exit_with_error(&err));
+                    let state =
+                        format_watson_state(&rows).unwrap_or_else(|err| exit_with_error(&err));
+                    match output {
+                        Some(dir) => {
+                            let existing: Vec<_> = ["frames", "state"]
+                                .iter()
+                                .map(|name| dir.join(name))
+                                .filter(|path| path.exists())
+                                .collect();
+                            if !force && !existing.is_empty() {
+                                let names: Vec<_> = existing
+                                    .iter()
+                                    .map(|path| path.display().to_string())
+                                    .collect();
+                                let verb = if names.len() == 1 { "exists" } else { "exist" };
+                                exit_with_error(&format!(
+                                    "{} already {}; rerun with --force to overwrite.",
+                                    names.join(" and "),
+                                    verb
+                                ));
+                            }
+                            std::fs::create_dir_all(&dir)
+                                .and_then(|_| std::fs::write(dir.join("frames"), frames))
+                                .and_then(|_| std::fs::write(dir.join("state"), state))
+                                .map_err(|err| err.to_string())
+                                .unwrap_or_else(|err| exit_with_error(&err));
+                            println!("Wrote Watson frames and state to {}", dir.display());
+                        }
+                        None => {
+                            println!("{}", frames);
+                            if rows.iter().any(|row| row.end_at.is_none()) {
+                                eprintln!(
+                                    "The running segment is not part of Watson frames; use --output DIR to also write the state file."
+                                );
+                            }
+                        }
+                    }
+                }
+                OutputFormat::Text | OutputFormat::Csv => {
+                    let options = csv_options(&csv).unwrap_or_else(|err| exit_with_error(&err));
+                    write_segments_csv(std::io::stdout().lock(), &rows, options)
+                        .unwrap_or_else(|err| exit_with_error(&err));
+                }
             }
-            println!("Total: {}", format_duration(total_seconds));
         }
         Command::Edit {
             id,
             index,
             name,
+            project,
             created_at,
             closed_at,
             segment_edit,
+            segment_add,
+            segment_delete,
+            segment_split,
+            segment_merge,
         } => {
-            let idx = match resolve_task_index(&store, now, id, index) {
+            let idx = match resolve_task_index(&store, now, id, index, "edit", false) {
                 Ok(idx) => idx,
                 Err(err) => exit_with_error(&err),
             };
 
+            let project_id = project.map(|name| {
+                if name.eq_ignore_ascii_case("none") {
+                    None
+                } else {
+                    Some(
+                        resolve_active_project(&store, &name)
+                            .unwrap_or_else(|err| exit_with_error(&err)),
+                    )
+                }
+            });
             let task = &mut store.tasks[idx];
-            let has_edits = name.is_some()
+            if let Some(project_id) = project_id.clone() {
+                task.project_id = project_id;
+            }
+            let segments = SegmentOperations {
+                edit: segment_edit,
+                add: segment_add,
+                delete: segment_delete,
+                split: segment_split,
+                merge: segment_merge,
+            };
+            let has_edits = project_id.is_some()
+                || name.is_some()
                 || created_at.is_some()
                 || closed_at.is_some()
-                || !segment_edit.is_empty();
+                || !segments.is_empty();
 
             if has_edits {
-                apply_task_edits(task, name, created_at, closed_at, segment_edit, now)
+                apply_task_edits(task, name, created_at, closed_at, segments, now)
                     .unwrap_or_else(|err| exit_with_error(&err));
             } else {
                 edit_task_interactive(task, now).unwrap_or_else(|err| exit_with_error(&err));
             }
 
-            save_store(&data_file, &store, &passphrase).unwrap_or_else(|err| exit_with_error(&err));
-            if is_new_store {
-                println!("Created encrypted data file at {}", data_file.display());
+            save_checked(
+                &data_file,
+                &store,
+                &unlocker,
+                force,
+                &snapshot,
+                &config.backup,
+            )
+            .unwrap_or_else(|err| exit_with_error(&err));
+        }
+        Command::Import {
+            source,
+            file,
+            dry_run,
+        } => {
+            let (entries, skipped) =
+                parse_import(source, &file).unwrap_or_else(|err| exit_with_error(&err));
+            let mut summary = apply_import(&mut store, entries);
+            summary.skipped.splice(0..0, skipped);
+            for line in &summary.skipped {
+                println!("Skipped {}", line);
+            }
+            for line in &summary.conflicts {
+                println!("Conflict {}", line);
+            }
+            println!(
+                "{} {} entries ({} duplicates, {} skipped, {} conflicts).",
+                if dry_run { "Would import" } else { "Imported" },
+                summary.imported,
+                summary.duplicates,
+                summary.skipped.len(),
+                summary.conflicts.len()
+            );
+            if !dry_run && summary.imported > 0 {
+                save_checked(
+                    &data_file,
+                    &store,
+                    &unlocker,
+                    force,
+                    &snapshot,
+                    &config.backup,
+                )
+                .unwrap_or_else(|err| exit_with_error(&err));
+            }
+        }
+        Command::Delete { id, index } => {
+            let idx = resolve_task_index(&store, now, id, index, "delete", false)
+                .unwrap_or_else(|err| exit_with_error(&err));
+            let task = &store.tasks[idx];
+            let prompt = format!(
+                "Delete task \"{}\" ({}) with {} segment(s), total {}? [y/N] ",
+                task.name,
+                task.id,
+                task.segments.len(),
+                format_duration(total_elapsed(task, now))
+            );
+            if !prompt_yes_no(&prompt) {
+                exit_with_error("Canceled.");
+            }
+            let task = store.tasks.remove(idx);
+            save_checked(
+                &data_file,
+                &store,
+                &unlocker,
+                force,
+                &snapshot,
+                &config.backup,
+            )
+            .unwrap_or_else(|err| exit_with_error(&err));
+            println!("Deleted: {} ({})", task.name, task.id);
+        }
+        Command::Archive { id, index } => {
+            let idx = resolve_task_index(&store, now, id, index, "archive", false)
+                .unwrap_or_else(|err| exit_with_error(&err));
+            let task = &mut store.tasks[idx];
+            if task.archived {
+                exit_with_error(&format!("Task \"{}\" is already archived.", task.name));
+            }
+            if task_status(task) != "stopped" {
+                exit_with_error("Stop the task before archiving it.");
+            }
+            task.archived = true;
+            let name = task.name.clone();
+            save_checked(
+                &data_file,
+                &store,
+                &unlocker,
+                force,
+                &snapshot,
+                &config.backup,
+            )
+            .unwrap_or_else(|err| exit_with_error(&err));
+            println!("Archived: {}", name);
+        }
+        Command::Unarchive { id, index } => {
+            let idx = resolve_task_index(&store, now, id, index, "unarchive", true)
+                .unwrap_or_else(|err| exit_with_error(&err));
+            let task = &mut store.tasks[idx];
+            if !task.archived {
+                exit_with_error(&format!("Task \"{}\" is not archived.", task.name));
+            }
+            task.archived = false;
+            let name = task.name.clone();
+            save_checked(
+                &data_file,
+                &store,
+                &unlocker,
+                force,
+                &snapshot,
+                &config.backup,
+            )
+            .unwrap_or_else(|err| exit_with_error(&err));
+            println!("Unarchived: {}", name);
+        }
+        Command::Merge { ids, into, by_name } => {
+            let plans = if by_name {
+                duplicate_name_plans(&store)
+            } else {
+                vec![MergePlan {
+                    target_id: into.unwrap_or_default(),
+                    source_ids: ids,
+                }]
+            };
+            if plans.is_empty() {
+                println!("No tasks share a name.");
+                return;
+            }
+            for plan in &plans {
+                let target = store
+                    .tasks
+                    .iter()
+                    .find(|task| task.id == plan.target_id)
+                    .unwrap_or_else(|| {
+                        exit_with_error(&format!("No task found with id \"{}\".", plan.target_id))
+                    });
+                println!(
+                    "Merge {} task(s) into \"{}\" ({})",
+                    plan.source_ids.len(),
+                    target.name,
+                    target.id
+                );
+            }
+            if !prompt_yes_no("Proceed? [y/N] ") {
+                exit_with_error("Canceled.");
+            }
+            for plan in &plans {
+                merge_tasks(&mut store, plan).unwrap_or_else(|err| exit_with_error(&err));
+            }
+            save_checked(
+                &data_file,
+                &store,
+                &unlocker,
+                force,
+                &snapshot,
+                &config.backup,
+            )
+            .unwrap_or_else(|err| exit_with_error(&err));
+            let merged: usize = plans.iter().map(|plan| plan.source_ids.len()).sum();
+            println!("Merged {} task(s).", merged);
+        }
+        Command::Doctor { apply } => {
+            let anomalies = find_anomalies(&store, now);
+            if anomalies.is_empty() {
+                println!("No problems found.");
+            } else {
+                println!("Found {} problem(s):", anomalies.len());
+                // Fixes shift segment positions, so rescan after each one.
+                let mut declined: Vec<AnomalyKey> = Vec::new();
+                let mut fixed = 0;
+                while let Some(anomaly) = find_anomalies(&store, now)
+                    .into_iter()
+                    .find(|anomaly| !declined.contains(&anomaly.key()))
+                {
+                    let accepted = if apply {
+                        println!("  - {}", anomaly);
+                        println!("    fix: {}", anomaly.fix_description());
+                        true
+                    } else {
+                        prompt_yes_no(&format!(
+                            "{}\n  {}? [y/N] ",
+                            anomaly,
+                            anomaly.fix_description()
+                        ))
+                    };
+                    if accepted {
+                        apply_fix(&mut store, anomaly.fix);
+                        fixed += 1;
+                    } else {
+                        declined.push(anomaly.key());
+                    }
+                }
+                if fixed > 0 {
+                    snapshot
+                        .ensure_unchanged(&data_file)
+                        .and_then(|()| save_store(&data_file, &store, &unlocker, &config.backup))
+                        .unwrap_or_else(|err| exit_with_error(&err));
+                }
+                println!(
+                    "Applied {} fix(es); {} problem(s) remain.",
+                    fixed,
+                    find_anomalies(&store, now).len()
+                );
             }
         }
+        Command::Project { action } => match action {
+            ProjectCommand::Add { name, client, rate } => {
+                add_project(&mut store, &name, client.as_deref(), rate)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                save_checked(
+                    &data_file,
+                    &store,
+                    &unlocker,
+                    force,
+                    &snapshot,
+                    &config.backup,
+                )
+                .unwrap_or_else(|err| exit_with_error(&err));
+                println!("Added project: {}", name.trim());
+            }
+            ProjectCommand::List { archived } => {
+                let projects: Vec<_> = store
+                    .projects
+                    .iter()
+                    .filter(|project| archived || !project.archived)
+                    .collect();
+                if projects.is_empty() {
+                    println!("No projects. Create one with \"ttt project add <name>\".");
+                    return;
+                }
+                for project in projects {
+                    let client = project_client(&store, project)
+                        .map(|client| format!(" client {}", client.name))
+                        .unwrap_or_default();
+                    let rate = project
+                        .hourly_rate
+                        .map(|rate| format!(" rate {:.2}/h", rate))
+                        .unwrap_or_default();
+                    let archived = if project.archived { " [archived]" } else { "" };
+                    println!(
+                        "{} ({}){}{}{}",
+                        project.name, project.id, client, rate, archived
+                    );
+                }
+            }
+            ProjectCommand::Archive { name } => {
+                set_project_archived(&mut store, &name, true)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                save_checked(
+                    &data_file,
+                    &store,
+                    &unlocker,
+                    force,
+                    &snapshot,
+                    &config.backup,
+                )
+                .unwrap_or_else(|err| exit_with_error(&err));
+                println!("Archived project: {}", name);
+            }
+            ProjectCommand::Unarchive { name } => {
+                set_project_archived(&mut store, &name, false)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                save_checked(
+                    &data_file,
+                    &store,
+                    &unlocker,
+                    force,
+                    &snapshot,
+                    &config.backup,
+                )
+                .unwrap_or_else(|err| exit_with_error(&err));
+                println!("Unarchived project: {}", name);
+            }
+        },
+        Command::Client { action } => match action {
+            ClientCommand::Add { name, rate } => {
+                add_client(&mut store, &name, rate).unwrap_or_else(|err| exit_with_error(&err));
+                save_checked(
+                    &data_file,
+                    &store,
+                    &unlocker,
+                    force,
+                    &snapshot,
+                    &config.backup,
+                )
+                .unwrap_or_else(|err| exit_with_error(&err));
+                println!("Added client: {}", name.trim());
+            }
+            ClientCommand::List { archived } => {
+                let clients: Vec<_> = store
+                    .clients
+                    .iter()
+                    .filter(|client| archived || !client.archived)
+                    .collect();
+                if clients.is_empty() {
+                    println!("No clients. Create one with \"ttt client add <name>\".");
+                    return;
+                }
+                for client in clients {
+                    let rate = client
+                        .hourly_rate
+                        .map(|rate| format!(" rate {:.2}/h", rate))
+                        .unwrap_or_default();
+                    let archived = if client.archived { " [archived]" } else { "" };
+                    println!("{} ({}){}{}", client.name, client.id, rate, archived);
+                }
+            }
+            ClientCommand::Archive { name } => {
+                set_client_archived(&mut store, &name, true)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                save_checked(
+                    &data_file,
+                    &store,
+                    &unlocker,
+                    force,
+                    &snapshot,
+                    &config.backup,
+                )
+                .unwrap_or_else(|err| exit_with_error(&err));
+                println!("Archived client: {}", name);
+            }
+            ClientCommand::Unarchive { name } => {
+                set_client_archived(&mut store, &name, false)
+                    .unwrap_or_else(|err| exit_with_error(&err));
+                save_checked(
+                    &data_file,
+                    &store,
+                    &unlocker,
+                    force,
+                    &snapshot,
+                    &config.backup,
+                )
+                .unwrap_or_else(|err| exit_with_error(&err));
+                println!("Unarchived client: {}", name);
+            }
+        },
         Command::Location => {}
         Command::Rekey => {}
-        Command::Restore => {}
+        Command::Agent { .. }
+        | Command::Encrypt
+        | Command::Decrypt
+        | Command::Keys { .. }
+        | Command::Recovery { .. }
+        | Command::Restore { .. } => {}
         Command::Version => {}
     }
 }
@@ -340,6 +1452,296 @@ fn exit_with_error(message: &str) -> ! {
     std::process::exit(2);
 }
 
+fn print_list_entry(position: usize, entry: &TaskListEntry) {
+    let archived = if entry.archived { ", archived" } else { "" };
+    println!(
+        "{:>3}) [{}{}] {}{} ({}) total {}",
+        position,
+        entry.status,
+        archived,
+        entry.name,
+        format_tags(&entry.tags),
+        entry.id,
+        format_duration(entry.seconds)
+    );
+}
+
+fn format_amount(amount: Option<f64>) -> String {
+    amount
+        .map(|value| format!(", {:.2}", value))
+        .unwrap_or_default()
+}
+
+fn format_total_amount(amount: Option<f64>) -> String {
+    amount
+        .map(|value| format!(" (amount {:.2})", value))
+        .unwrap_or_default()
+}
+
+fn sum_amounts(amounts: impl IntoIterator<Item = Option<f64>>) -> Option<f64> {
+    amounts
+        .into_iter()
+        .flatten()
+        .fold(None, |total, value| Some(total.unwrap_or(0.0) + value))
+}
+
+fn format_tags(tags: &[String]) -> String {
+    tags.iter().map(|tag| format!(" +{}", tag)).collect()
+}
+
+fn start_new(
+    store: &mut Store,
+    name: String,
+    tags: Vec<String>,
+    project_id: Option<String>,
+    at: DateTime<Utc>,
+) {
+    let current = current_task_state(store);
+    validate_open_time(store, current.map(|(idx, _)| idx), at)
+        .unwrap_or_else(|err| exit_with_error(&err));
+    stop_current_for(store, &name, at);
+    start_task(store, name, tags, project_id, at);
+}
+
+fn continue_existing(store: &mut Store, idx: usize, at: DateTime<Utc>) {
+    if let Some((current, _)) = current_task_state(store) {
+        if current == idx {
+            exit_with_error(&format!(
+                "Task \"{}\" is already running or paused. Use \"ttt resume\" instead.",
+                store.tasks[idx].name
+            ));
+        }
+        validate_close_time(&store.tasks[current], at).unwrap_or_else(|err| exit_with_error(&err));
+    }
+    if store.tasks[idx].closed_at.is_none() {
+        exit_with_error(&format!(
+            "Task \"{}\" is not stopped.",
+            store.tasks[idx].name
+        ));
+    }
+    let name = store.tasks[idx].name.clone();
+    stop_current_for(store, &name, at);
+    validate_open_time(store, Some(idx), at).unwrap_or_else(|err| exit_with_error(&err));
+    continue_task(store, idx, at);
+}
+
+fn stop_current_for(store: &mut Store, next_name: &str, at: DateTime<Utc>) {
+    let Some((idx, state)) = current_task_state(store) else {
+        return;
+    };
+    let existing_name = &store.tasks[idx].name;
+    let prompt = match state {
+        TaskState::Active => format!(
+            "Active task \"{}\" is running. Stop it and start \"{}\"? [y/N] ",
+            existing_name, next_name
+        ),
+        TaskState::Paused => format!(
+            "Task \"{}\" is paused. Abandon it and start \"{}\"? [y/N] ",
+            existing_name, next_name
+        ),
+    };
+    if !prompt_yes_no(&prompt) {
+        exit_with_error("Canceled.");
+    }
+    stop_task(store, idx, at);
+}
+
+fn print_continued(task: &Task, at: DateTime<Utc>, now: DateTime<Utc>) {
+    println!(
+        "Continued: {}{} at {} (total {})",
+        task.name,
+        format_tags(&task.tags),
+        format_time_local_display(at),
+        format_duration(total_elapsed(task, now))
+    );
+}
+
+fn save_checked(
+    path: &Path,
+    store: &Store,
+    unlocker: &Unlocker,
+    force: bool,
+    snapshot: &Snapshot,
+    backups: &BackupConfig,
+) -> Result<(), String> {
+    snapshot.ensure_unchanged(path)?;
+    let violations = validate_store(store);
+    if !violations.is_empty() {
+        if !force {
+            return Err(format!(
+                "{}\nNothing was saved. Fix the data (see \"ttt doctor\") or rerun with --force to save anyway.",
+                format_violations(&violations)
+            ));
+        }
+        eprintln!(
+            "Warning: saving despite {} consistency problem(s) (--force).",
+            violations.len()
+        );
+    }
+    save_store(path, store, unlocker, backups)?;
+    if let Some(notice) = creation_notice(snapshot, path, unlocker) {
+        println!("{}", notice);
+    }
+    Ok(())
+}
+
+/// Announces a data file that the save just created, with its recovery codes. Every command
+/// that can create the file saves through `save_checked`, so the codes are shown exactly once.
+fn creation_notice(snapshot: &Snapshot, data_file: &Path, unlocker: &Unlocker) -> Option<String> {
+    if !snapshot.is_missing() {
+        return None;
+    }
+    if unlocker.encryption() == Encryption::None {
+        return Some(format!(
+            "Created data file at {} (not encrypted)",
+            data_file.display()
+        ));
+    }
+    let mut notice = format!("Created encrypted data file at {}", data_file.display());
+    let codes = unlocker.take_recovery_codes();
+    if !codes.is_empty() {
+        notice.push('\n');
+        notice.push_str(&format_recovery_codes(data_file, &codes));
+    }
+    Some(notice)
+}
+
+fn print_recovery_codes(data_file: &Path, codes: &[String]) {
+    if !codes.is_empty() {
+        println!("{}", format_recovery_codes(data_file, codes));
+    }
+}
+
+fn format_recovery_codes(data_file: &Path, codes: &[String]) -> String {
+    let mut text = format!(
+        "Recovery codes for {}. Each one resets a forgotten passphrase once with \"ttt recovery use\"; print them or store them offline, they are not shown again:",
+        data_file.display()
+    );
+    for code in codes {
+        text.push_str(&format!("\n  {}", code));
+    }
+    text
+}
+
+/// Reports keyfile slots that a data key rotation dropped and locks the agent, whose cached key
+/// no longer opens the data file.
+fn finish_key_rotation(data_file: &Path, dropped_keyfiles: usize) {
+    if dropped_keyfiles > 0 {
+        println!(
+            "Removed {} keyfile unlock method(s) whose keyfile was not given; add them again with \"ttt keys add-keyfile\".",
+            dropped_keyfiles
+        );
+    }
+    if let Some(client) = AgentClient::find(data_file)
+        && client.lock().is_ok()
+    {
+        println!("Locked the agent; it held the old data key.");
+    }
+}
+
+/// Locks the data file and unlocks it without the agent, so that the data key is available for
+/// wrapping in new key slots. Returns the lock, the snapshot and the envelope that was read.
+fn unlock_for_key_change(data_file: &Path, unlocker: &Unlocker) -> (DataLock, Snapshot, String) {
+    unlocker
+        .prepare(data_file)
+        .unwrap_or_else(|err| exit_with_error(&err));
+    let lock = lock_data_file(data_file, LOCK_WAIT).unwrap_or_else(|err| exit_with_error(&err));
+    let snapshot = Snapshot::take(data_file).unwrap_or_else(|err| exit_with_error(&err));
+    let payload = std::fs::read_to_string(data_file)
+        .map_err(|err| format!("Could not read {}: {}", data_file.display(), err))
+        .unwrap_or_else(|err| exit_with_error(&err));
+    ensure_encrypted(data_file, &payload);
+    unlocker
+        .open(&payload)
+        .unwrap_or_else(|err| exit_with_error(&err));
+    (lock, snapshot, payload)
+}
+
+/// Unlocks the data file, lets `change` edit its key slots and saves the envelope with the
+/// edited slots around the unchanged ciphertext, then prints the message `change` returns.
+fn change_key_slots(
+    data_file: &Path,
+    unlocker: &Unlocker,
+    backups: &BackupConfig,
+    change: impl FnOnce(&mut Vec<KeySlot>) -> String,
+) {
+    let (_lock, snapshot, payload) = unlock_for_key_change(data_file, unlocker);
+    // Files from before key slots get their passphrase slot first.
+    unlocker
+        .with_key(|_| Ok(()))
+        .unwrap_or_else(|err| exit_with_error(&err));
+    let mut slots = unlocker.slots();
+    let message = change(&mut slots);
+    snapshot
+        .ensure_unchanged(data_file)
+        .and_then(|()| replace_slots(&payload, slots))
+        .and_then(|updated| save_payload(data_file, &updated, backups))
+        .unwrap_or_else(|err| exit_with_error(&err));
+    println!("{}", message);
+}
+
+fn ensure_encrypted(data_file: &Path, payload: &str) {
+    if !is_encrypted(payload) {
+        exit_with_error(&format!(
+            "{} is not encrypted; run \"ttt encrypt\" first.",
+            data_file.display()
+        ));
+    }
+}
+
+fn event_time(args: &EventTimeArgs, now: DateTime<Utc>) -> DateTime<Utc> {
+    resolve_event_time(args.at.as_deref(), args.ago.as_deref(), now)
+        .unwrap_or_else(|err| exit_with_error(&err))
+}
+
+fn csv_options(args: &CsvArgs) -> Result<CsvOptions, String> {
+    Ok(CsvOptions {
+        delimiter: parse_delimiter(&args.delimiter)?,
+        header: !args.no_header,
+    })
+}
+
+fn resolve_report_window(
+    today: bool,
+    yesterday: bool,
+    week: bool,
+    month: bool,
+    from: Option<String>,
+    to: Option<String>,
+    now: DateTime<Utc>,
+) -> Result<ReportWindow, String> {
+    let selected = [today, yesterday, week, month, from.is_some()]
+        .iter()
+        .filter(|flag| **flag)
+        .count();
+    if selected > 1 {
+        return Err("Use only one of --today, --yesterday, --week, --month or --from.".into());
+    }
+    if to.is_some() && from.is_none() {
+        return Err("Use --to together with --from.".into());
+    }
+    if let Some(from) = from {
+        let from = parse_date_input(&from, "--from")?;
+        let to = match to {
+            Some(to) => parse_date_input(&to, "--to")?,
+            None => local_date(now),
+        };
+        if to < from {
+            return Err("--to must not be earlier than --from.".into());
+        }
+        return Ok(ReportWindow::Range { from, to });
+    }
+    Ok(if yesterday {
+        ReportWindow::Yesterday
+    } else if week {
+        ReportWindow::Week
+    } else if month {
+        ReportWindow::Month
+    } else {
+        ReportWindow::Today
+    })
+}
+
 fn active_segment_start(task: &Task) -> Option<chrono::DateTime<Utc>> {
     task.segments
         .iter()
@@ -354,19 +1756,84 @@ fn last_segment_end(task: &Task) -> Option<chrono::DateTime<Utc>> {
         .find_map(|segment| segment.end_at)
 }
 
-fn format_backup_entry(entry: &crate::storage::BackupEntry) -> String {
+fn print_restore_diff(diff: &StoreDiff) {
+    if diff.changes.is_empty() {
+        println!("No task changes compared with the current data.");
+    } else {
+        println!("Changes compared with the current data:");
+        for change in &diff.changes {
+            println!("  {}", change);
+        }
+    }
+    println!("Total time: {}", format_signed_duration(diff.seconds_delta));
+}
+
+fn format_backup_entry(
+    entry: &crate::storage::BackupEntry,
+    summary: &Result<BackupSummary, String>,
+) -> String {
     let name = entry
         .path
         .file_name()
         .and_then(|value| value.to_str())
         .unwrap_or("backup");
-    let modified = entry
-        .modified
-        .map(|time| {
-            DateTime::<Local>::from(time)
-                .format("%Y-%m-%d %H:%M:%S")
-                .to_string()
-        })
-        .unwrap_or_else(|| "unknown".to_string());
-    format!("{} (modified {}, {} bytes)", name, modified, entry.size)
+    let local = |time: DateTime<Utc>| {
+        time.with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    };
+    let created = local(entry.created_at);
+    match summary {
+        Ok(summary) => format!(
+            "{}  {} task(s), last activity {}  ({})",
+            created,
+            summary.tasks,
+            summary
+                .latest_activity
+                .map(local)
+                .unwrap_or_else(|| "none".to_string()),
+            name
+        ),
+        Err(_) => format!("{}  cannot be decrypted  ({})", created, name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::PassphraseSources;
+
+    #[test]
+    fn creating_the_data_file_announces_its_recovery_codes() {
+        let dir = std::env::temp_dir().join(format!("ttt-created-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let passphrase = dir.join("passphrase");
+        std::fs::write(&passphrase, "secret\n").unwrap();
+        let data_file = dir.join("ttt.json");
+        let unlocker = Unlocker::new(
+            PassphraseSources {
+                file: Some(passphrase),
+                ..PassphraseSources::default()
+            },
+            None,
+            false,
+            None,
+        );
+        let backups = BackupConfig::default();
+
+        // "ttt project add" on a fresh file saves a store without tasks.
+        let snapshot = Snapshot::take(&data_file).unwrap();
+        let mut store = Store::default();
+        add_project(&mut store, "Website", None, None).unwrap();
+        save_store(&data_file, &store, &unlocker, &backups).unwrap();
+        let notice = creation_notice(&snapshot, &data_file, &unlocker).unwrap();
+        let codes = notice.lines().filter(|line| line.starts_with("  ")).count();
+        assert!(notice.starts_with("Created encrypted data file"));
+        assert_eq!(codes, 8);
+
+        let snapshot = Snapshot::take(&data_file).unwrap();
+        save_store(&data_file, &store, &unlocker, &backups).unwrap();
+        assert!(creation_notice(&snapshot, &data_file, &unlocker).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
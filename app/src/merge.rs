@@ -0,0 +1,165 @@
+use chrono::{DateTime, Utc};
+
+use crate::model::{Store, Task};
+use crate::tasks::normalize_task_name;
+
+pub struct MergePlan {
+    pub target_id: String,
+    pub source_ids: Vec<String>,
+}
+
+/// Groups tasks whose normalized names match. The open or most recently active task of each
+/// group is the merge target.
+pub fn duplicate_name_plans(store: &Store) -> Vec<MergePlan> {
+    let mut groups: Vec<(String, Vec<&Task>)> = Vec::new();
+    for task in &store.tasks {
+        let key = normalize_task_name(&task.name);
+        match groups.iter_mut().find(|(name, _)| *name == key) {
+            Some((_, tasks)) => tasks.push(task),
+            None => groups.push((key, vec![task])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|(_, tasks)| tasks.len() > 1)
+        .map(|(_, tasks)| {
+            let target = tasks
+                .iter()
+                .max_by_key(|task| (task.closed_at.is_none(), last_activity(task)))
+                .unwrap();
+            MergePlan {
+                target_id: target.id.clone(),
+                source_ids: tasks
+                    .iter()
+                    .filter(|task| task.id != target.id)
+                    .map(|task| task.id.clone())
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Moves every segment of the source tasks into the target and removes the sources.
+pub fn merge_tasks(store: &mut Store, plan: &MergePlan) -> Result<(), String> {
+    let find = |id: &str| {
+        store
+            .tasks
+            .iter()
+            .position(|task| task.id == id)
+            .ok_or_else(|| format!("No task found with id \"{}\".", id))
+    };
+    find(&plan.target_id)?;
+    let mut source_idxs = Vec::new();
+    for id in &plan.source_ids {
+        if *id == plan.target_id {
+            return Err("A task cannot be merged into itself.".into());
+        }
+        let idx = find(id)?;
+        if !source_idxs.contains(&idx) {
+            source_idxs.push(idx);
+        }
+    }
+    if source_idxs.is_empty() {
+        return Err("Name at least one task to merge.".into());
+    }
+
+    source_idxs.sort_unstable();
+    let sources: Vec<Task> = source_idxs
+        .into_iter()
+        .rev()
+        .map(|idx| store.tasks.remove(idx))
+        .collect();
+    let target = store
+        .tasks
+        .iter_mut()
+        .find(|task| task.id == plan.target_id)
+        .unwrap();
+
+    let mut still_running = target.closed_at.is_none();
+    for source in sources {
+        still_running |= source.closed_at.is_none();
+        target.created_at = target.created_at.min(source.created_at);
+        target.closed_at = target.closed_at.max(source.closed_at);
+        for tag in source.tags {
+            if !target.tags.contains(&tag) {
+                target.tags.push(tag);
+            }
+        }
+        if target.project_id.is_none() {
+            target.project_id = source.project_id;
+        }
+        target.segments.extend(source.segments);
+    }
+    target.segments.sort_by_key(|segment| segment.start_at);
+    if still_running {
+        target.closed_at = None;
+    } else {
+        target.closed_at = target.closed_at.max(last_activity(target));
+    }
+    Ok(())
+}
+
+fn last_activity(task: &Task) -> Option<DateTime<Utc>> {
+    task.segments
+        .iter()
+        .flat_map(|segment| [Some(segment.start_at), segment.end_at])
+        .flatten()
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::model::Segment;
+
+    #[test]
+    fn by_name_merge_moves_segments_into_the_latest_task() {
+        let nine = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let at = |minutes: i64| nine + Duration::minutes(minutes);
+        let task = |id: &str, name: &str, tag: &str, start: i64| Task {
+            id: id.into(),
+            name: name.into(),
+            tags: vec![tag.into()],
+            project_id: None,
+            archived: false,
+            created_at: at(start),
+            closed_at: Some(at(start + 30)),
+            segments: vec![Segment {
+                start_at: at(start),
+                end_at: Some(at(start + 30)),
+            }],
+        };
+        let mut store = Store {
+            tasks: vec![
+                task("a", "Code review", "acme", 60),
+                task("b", "Write docs", "docs", 0),
+                task("c", "  code  REVIEW ", "review", 120),
+                task("d", "code review", "acme", 0),
+            ],
+            ..Store::default()
+        };
+
+        let plans = duplicate_name_plans(&store);
+        assert_eq!(plans.len(), 1);
+        assert_eq!(plans[0].target_id, "c");
+        assert_eq!(plans[0].source_ids, vec!["a", "d"]);
+
+        merge_tasks(&mut store, &plans[0]).unwrap();
+        assert_eq!(store.tasks.len(), 2);
+        let merged = store.tasks.iter().find(|task| task.id == "c").unwrap();
+        let starts: Vec<_> = merged.segments.iter().map(|s| s.start_at).collect();
+        assert_eq!(starts, vec![at(0), at(60), at(120)]);
+        assert_eq!(merged.created_at, at(0));
+        assert_eq!(merged.closed_at, Some(at(150)));
+        assert_eq!(merged.tags, vec!["review", "acme"]);
+
+        let into_self = MergePlan {
+            target_id: "b".into(),
+            source_ids: vec!["b".into()],
+        };
+        assert!(merge_tasks(&mut store, &into_self).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value, json};
+
+use crate::model::{STORE_VERSION, Store};
+
+/// Upgrades the raw JSON of a store from `from` to `from + 1`.
+struct Migration {
+    from: u32,
+    description: &'static str,
+    apply: fn(&mut Map<String, Value>),
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        description: "add task tags",
+        apply: |store| set_task_default(store, "tags", json!([])),
+    },
+    Migration {
+        from: 2,
+        description: "add projects, clients and task project_id",
+        apply: |store| {
+            store.entry("projects").or_insert(json!([]));
+            store.entry("clients").or_insert(json!([]));
+            set_task_default(store, "project_id", Value::Null);
+        },
+    },
+    Migration {
+        from: 3,
+        description: "add task archived flag",
+        apply: |store| set_task_default(store, "archived", json!(false)),
+    },
+];
+
+fn set_task_default(store: &mut Map<String, Value>, key: &str, value: Value) {
+    let tasks = store
+        .get_mut("tasks")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten();
+    for task in tasks.filter_map(Value::as_object_mut) {
+        task.entry(key).or_insert_with(|| value.clone());
+    }
+}
+
+/// Brings decrypted store JSON of any older version up to `STORE_VERSION`, appending one entry
+/// to the store's `migrations` history per step. Stores written by a newer ttt are refused.
+pub fn migrate_store(value: Value, now: DateTime<Utc>) -> Result<Store, String> {
+    let Value::Object(mut store) = value else {
+        return Err("Invalid data file: expected a JSON object.".into());
+    };
+    let version = match store.get("version") {
+        None => 1,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or("Invalid data file: version is not a number.")?,
+    };
+    if version == 0 {
+        return Err("Invalid data file: version 0.".into());
+    }
+    if version > STORE_VERSION {
+        return Err(format!(
+            "Data file version {} is newer than this ttt supports ({}). Upgrade ttt to open it.",
+            version, STORE_VERSION
+        ));
+    }
+
+    let mut records = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|step| step.from >= version) {
+        (migration.apply)(&mut store);
+        records.push(json!({
+            "from": migration.from,
+            "to": migration.from + 1,
+            "description": migration.description,
+            "migrated_at": now,
+        }));
+    }
+    if !records.is_empty() {
+        let history = store.entry("migrations").or_insert(json!([]));
+        if let Some(history) = history.as_array_mut() {
+            history.extend(records);
+        }
+    }
+    store.insert("version".into(), json!(STORE_VERSION));
+
+    serde_json::from_value(Value::Object(store))
+        .map_err(|err| format!("Invalid data file (version {}): {}", version, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn every_historical_fixture_migrates_to_the_current_version() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        let fixtures = [
+            (1, include_str!("../tests/fixtures/store-v1.json")),
+            (2, include_str!("../tests/fixtures/store-v2.json")),
+            (3, include_str!("../tests/fixtures/store-v3.json")),
+            (4, include_str!("../tests/fixtures/store-v4.json")),
+        ];
+        assert_eq!(fixtures.len() as u32, STORE_VERSION);
+        assert_eq!(MIGRATIONS.len() as u32, STORE_VERSION - 1);
+
+        for (version, text) in fixtures {
+            let store = migrate_store(serde_json::from_str(text).unwrap(), now).unwrap();
+            assert_eq!(store.version, STORE_VERSION);
+            assert_eq!(store.tasks.len(), 2, "fixture v{}", version);
+            let steps: Vec<(u32, u32)> = store
+                .migrations
+                .iter()
+                .map(|record| (record.from, record.to))
+                .collect();
+            let expected: Vec<(u32, u32)> = (version..STORE_VERSION).map(|v| (v, v + 1)).collect();
+            assert_eq!(steps, expected, "fixture v{}", version);
+            assert!(
+                store
+                    .migrations
+                    .iter()
+                    .all(|record| record.migrated_at == now)
+            );
+        }
+
+        let v3 = include_str!("../tests/fixtures/store-v3.json");
+        let store = migrate_store(serde_json::from_str(v3).unwrap(), now).unwrap();
+        assert_eq!(store.clients[0].name, "Acme");
+        assert!(store.tasks[0].project_id.is_some());
+        assert!(!store.tasks[0].archived);
+
+        let err =
+            migrate_store(json!({ "version": STORE_VERSION + 1, "tasks": [] }), now).unwrap_err();
+        assert!(err.contains("newer than this ttt supports"));
+    }
+}
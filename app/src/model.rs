@@ -1,38 +1,98 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+pub const STORE_VERSION: u32 = 4;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Store {
     pub version: u32,
     pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub projects: Vec<Project>,
+    #[serde(default)]
+    pub clients: Vec<Client>,
+    #[serde(default)]
+    pub migrations: Vec<MigrationRecord>,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Store {
+            version: STORE_VERSION,
+            tasks: Vec::new(),
+            projects: Vec::new(),
+            clients: Vec::new(),
+            migrations: Vec::new(),
+        }
+    }
+}
+
+/// One upgrade step applied to the store when it was loaded by a newer ttt.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MigrationRecord {
+    pub from: u32,
+    pub to: u32,
+    pub description: String,
+    pub migrated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Task {
     pub id: String,
     pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub archived: bool,
     pub created_at: DateTime<Utc>,
     pub closed_at: Option<DateTime<Utc>>,
     pub segments: Vec<Segment>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    pub client_id: Option<String>,
+    pub hourly_rate: Option<f64>,
+    pub archived: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Client {
+    pub id: String,
+    pub name: String,
+    pub hourly_rate: Option<f64>,
+    pub archived: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Segment {
     pub start_at: DateTime<Utc>,
     pub end_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct ReportEntry {
     pub name: String,
     pub start_at: DateTime<Utc>,
     pub end_at: DateTime<Utc>,
     pub seconds: i64,
+    pub amount: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportDay {
+    pub date: NaiveDate,
+    pub entries: Vec<ReportEntry>,
 }
 
 pub type SegmentEdit = (usize, DateTime<Utc>, Option<DateTime<Utc>>);
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TaskState {
     Active,
     Paused,
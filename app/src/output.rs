@@ -0,0 +1,197 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::export::SegmentRow;
+use crate::list::TaskListEntry;
+use crate::model::{ReportDay, ReportEntry, TaskState};
+use crate::report::ReportGroup;
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Ndjson,
+    Csv,
+    Timewarrior,
+    Watson,
+}
+
+#[derive(Serialize)]
+struct Document<'a, T: Serialize> {
+    schema_version: u32,
+    kind: &'static str,
+    #[serde(flatten)]
+    data: &'a T,
+}
+
+#[derive(Serialize)]
+pub struct ListOutput<'a> {
+    pub window: &'static str,
+    pub total_seconds: i64,
+    pub tasks: &'a [TaskListEntry],
+}
+
+#[derive(Serialize)]
+pub struct ReportOutput<'a> {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub group: ReportGroup,
+    pub total_seconds: i64,
+    pub total_amount: Option<f64>,
+    pub days: &'a [ReportDay],
+}
+
+#[derive(Serialize)]
+struct ExportOutput<'a> {
+    segments: &'a [SegmentRow],
+}
+
+#[derive(Serialize)]
+pub struct StatusOutput {
+    pub state: Option<TaskState>,
+    pub task: Option<StatusTask>,
+    pub elapsed_seconds: i64,
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct StatusTask {
+    pub id: String,
+    pub name: String,
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DatedReportEntry<'a> {
+    date: NaiveDate,
+    #[serde(flatten)]
+    entry: &'a ReportEntry,
+}
+
+#[derive(Serialize)]
+struct Total {
+    total_seconds: i64,
+    total_amount: Option<f64>,
+}
+
+pub fn print_list(format: OutputFormat, output: &ListOutput) -> Result<(), String> {
+    match format {
+        OutputFormat::Ndjson => {
+            for entry in output.tasks {
+                print_record("task", entry, false)?;
+            }
+            print_record(
+                "total",
+                &Total {
+                    total_seconds: output.total_seconds,
+                    total_amount: None,
+                },
+                false,
+            )
+        }
+        _ => print_record("list", output, true),
+    }
+}
+
+pub fn print_report(format: OutputFormat, output: &ReportOutput) -> Result<(), String> {
+    match format {
+        OutputFormat::Ndjson => {
+            for day in output.days {
+                for entry in &day.entries {
+                    let record = DatedReportEntry {
+                        date: day.date,
+                        entry,
+                    };
+                    print_record("entry", &record, false)?;
+                }
+            }
+            print_record(
+                "total",
+                &Total {
+                    total_seconds: output.total_seconds,
+                    total_amount: output.total_amount,
+                },
+                false,
+            )
+        }
+        _ => print_record("report", output, true),
+    }
+}
+
+pub fn print_segments(format: OutputFormat, rows: &[SegmentRow]) -> Result<(), String> {
+    match format {
+        OutputFormat::Ndjson => {
+            for row in rows {
+                print_record("segment", row, false)?;
+            }
+            Ok(())
+        }
+        _ => print_record("export", &ExportOutput { segments: rows }, true),
+    }
+}
+
+pub fn print_status(format: OutputFormat, output: &StatusOutput) -> Result<(), String> {
+    print_record("status", output, format != OutputFormat::Ndjson)
+}
+
+fn print_record<T: Serialize>(kind: &'static str, data: &T, pretty: bool) -> Result<(), String> {
+    let document = Document {
+        schema_version: SCHEMA_VERSION,
+        kind,
+        data,
+    };
+    let line = if pretty {
+        serde_json::to_string_pretty(&document)
+    } else {
+        serde_json::to_string(&document)
+    }
+    .map_err(|err| err.to_string())?;
+    println!("{}", line);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_document_uses_stable_field_names() {
+        let entries = vec![TaskListEntry {
+            name: "Review".into(),
+            id: "id-1".into(),
+            tags: vec!["acme".into()],
+            project: None,
+            client: None,
+            status: "stopped",
+            archived: false,
+            seconds: 90,
+            start_at: Some(
+                DateTime::parse_from_rfc3339("2025-01-01T09:00:00Z")
+                    .unwrap()
+                    .into(),
+            ),
+            end_at: None,
+        }];
+        let output = ListOutput {
+            window: "all",
+            total_seconds: 90,
+            tasks: &entries,
+        };
+        let document = Document {
+            schema_version: SCHEMA_VERSION,
+            kind: "list",
+            data: &output,
+        };
+        let value = serde_json::to_value(&document).unwrap();
+        assert_eq!(value["schema_version"], 1);
+        assert_eq!(value["kind"], "list");
+        assert_eq!(value["total_seconds"], 90);
+        assert_eq!(value["tasks"][0]["seconds"], 90);
+        assert_eq!(value["tasks"][0]["start_at"], "2025-01-01T09:00:00Z");
+        assert_eq!(value["tasks"][0]["tags"][0], "acme");
+    }
+}
@@ -0,0 +1,189 @@
+use uuid::Uuid;
+
+use crate::model::{Client, Project, Store, Task};
+
+pub const NO_PROJECT_LABEL: &str = "(no project)";
+pub const NO_CLIENT_LABEL: &str = "(no client)";
+
+pub fn add_client(store: &mut Store, name: &str, hourly_rate: Option<f64>) -> Result<(), String> {
+    let name = validate_name(name, "Client")?;
+    validate_rate(hourly_rate)?;
+    if find_client_index(store, &name).is_some() {
+        return Err(format!("Client \"{}\" already exists.", name));
+    }
+    store.clients.push(Client {
+        id: Uuid::new_v4().to_string(),
+        name,
+        hourly_rate,
+        archived: false,
+    });
+    Ok(())
+}
+
+pub fn add_project(
+    store: &mut Store,
+    name: &str,
+    client: Option<&str>,
+    hourly_rate: Option<f64>,
+) -> Result<(), String> {
+    let name = validate_name(name, "Project")?;
+    validate_rate(hourly_rate)?;
+    if find_project_index(store, &name).is_some() {
+        return Err(format!("Project \"{}\" already exists.", name));
+    }
+    let client_id = match client {
+        Some(client) => {
+            let idx = find_client_index(store, client).ok_or_else(|| {
+                format!(
+                    "No client named \"{}\". Create it with \"ttt client add\".",
+                    client
+                )
+            })?;
+            if store.clients[idx].archived {
+                return Err(format!(
+                    "Client \"{}\" is archived.",
+                    store.clients[idx].name
+                ));
+            }
+            Some(store.clients[idx].id.clone())
+        }
+        None => None,
+    };
+    store.projects.push(Project {
+        id: Uuid::new_v4().to_string(),
+        name,
+        client_id,
+        hourly_rate,
+        archived: false,
+    });
+    Ok(())
+}
+
+pub fn set_project_archived(store: &mut Store, name: &str, archived: bool) -> Result<(), String> {
+    let idx =
+        find_project_index(store, name).ok_or_else(|| format!("No project named \"{}\".", name))?;
+    store.projects[idx].archived = archived;
+    Ok(())
+}
+
+pub fn set_client_archived(store: &mut Store, name: &str, archived: bool) -> Result<(), String> {
+    let idx =
+        find_client_index(store, name).ok_or_else(|| format!("No client named \"{}\".", name))?;
+    store.clients[idx].archived = archived;
+    Ok(())
+}
+
+pub fn resolve_active_project(store: &Store, name: &str) -> Result<String, String> {
+    let idx = find_project_index(store, name).ok_or_else(|| {
+        format!(
+            "No project named \"{}\". Create it with \"ttt project add\".",
+            name
+        )
+    })?;
+    let project = &store.projects[idx];
+    if project.archived {
+        return Err(format!("Project \"{}\" is archived.", project.name));
+    }
+    Ok(project.id.clone())
+}
+
+pub fn find_project_index(store: &Store, name_or_id: &str) -> Option<usize> {
+    let needle = name_or_id.trim();
+    store
+        .projects
+        .iter()
+        .position(|project| project.id == needle || project.name.eq_ignore_ascii_case(needle))
+}
+
+pub fn find_client_index(store: &Store, name_or_id: &str) -> Option<usize> {
+    let needle = name_or_id.trim();
+    store
+        .clients
+        .iter()
+        .position(|client| client.id == needle || client.name.eq_ignore_ascii_case(needle))
+}
+
+pub fn task_project<'a>(store: &'a Store, task: &Task) -> Option<&'a Project> {
+    let project_id = task.project_id.as_deref()?;
+    store
+        .projects
+        .iter()
+        .find(|project| project.id == project_id)
+}
+
+pub fn project_client<'a>(store: &'a Store, project: &Project) -> Option<&'a Client> {
+    let client_id = project.client_id.as_deref()?;
+    store.clients.iter().find(|client| client.id == client_id)
+}
+
+pub fn task_project_name(store: &Store, task: &Task) -> Option<String> {
+    task_project(store, task).map(|project| project.name.clone())
+}
+
+pub fn task_client_name(store: &Store, task: &Task) -> Option<String> {
+    task_project(store, task)
+        .and_then(|project| project_client(store, project))
+        .map(|client| client.name.clone())
+}
+
+pub fn task_hourly_rate(store: &Store, task: &Task) -> Option<f64> {
+    let project = task_project(store, task)?;
+    project
+        .hourly_rate
+        .or_else(|| project_client(store, project).and_then(|client| client.hourly_rate))
+}
+
+pub fn billable_amount(seconds: i64, hourly_rate: Option<f64>) -> Option<f64> {
+    hourly_rate.map(|rate| seconds as f64 / 3600.0 * rate)
+}
+
+fn validate_name(name: &str, label: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!("{} name cannot be empty.", label));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_rate(hourly_rate: Option<f64>) -> Result<(), String> {
+    match hourly_rate {
+        Some(rate) if !rate.is_finite() || rate < 0.0 => {
+            Err("Hourly rate must be a non-negative number.".into())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_rate_falls_back_to_client_rate() {
+        let mut store = Store::default();
+        add_client(&mut store, "Acme", Some(100.0)).unwrap();
+        add_project(&mut store, "Website", Some("acme"), None).unwrap();
+        add_project(&mut store, "Audit", Some("Acme"), Some(150.0)).unwrap();
+        assert!(add_project(&mut store, "website", None, None).is_err());
+
+        let task = |project: &str| Task {
+            id: project.into(),
+            name: project.into(),
+            tags: Vec::new(),
+            project_id: Some(resolve_active_project(&store, project).unwrap()),
+            archived: false,
+            created_at: chrono::Utc::now(),
+            closed_at: None,
+            segments: Vec::new(),
+        };
+        assert_eq!(task_hourly_rate(&store, &task("Website")), Some(100.0));
+        assert_eq!(task_hourly_rate(&store, &task("Audit")), Some(150.0));
+        assert_eq!(
+            task_client_name(&store, &task("Audit")),
+            Some("Acme".to_string())
+        );
+
+        set_project_archived(&mut store, "audit", true).unwrap();
+        assert!(resolve_active_project(&store, "Audit").is_err());
+    }
+}
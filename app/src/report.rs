@@ -1,22 +1,98 @@
-use chrono::{DateTime, Duration, Local, TimeZone, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
 
-use crate::model::{ReportEntry, Segment, Store};
+use crate::model::{ReportDay, ReportEntry, Segment, Store};
+use crate::projects::{
+    NO_CLIENT_LABEL, NO_PROJECT_LABEL, billable_amount, task_client_name, task_hourly_rate,
+    task_project_name,
+};
+use crate::time::{local_date, local_day_start, month_end, month_start, week_start};
 
-pub fn report_today(store: &Store, now: DateTime<Utc>) -> Vec<ReportEntry> {
-    let now_local = now.with_timezone(&Local);
-    let date = now_local.date_naive();
-    let start_local = date.and_hms_opt(0, 0, 0).unwrap();
-    let end_local = start_local + Duration::days(1);
+const UNTAGGED_LABEL: &str = "(untagged)";
 
-    let start_utc = Local
-        .from_local_datetime(&start_local)
-        .unwrap()
-        .with_timezone(&Utc);
-    let end_utc = Local
-        .from_local_datetime(&end_local)
-        .unwrap()
-        .with_timezone(&Utc);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportGroup {
+    Task,
+    Tag,
+    Project,
+    Client,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportWindow {
+    Today,
+    Yesterday,
+    Week,
+    Month,
+    Range { from: NaiveDate, to: NaiveDate },
+}
+
+impl ReportWindow {
+    pub fn dates(self, now: DateTime<Utc>) -> (NaiveDate, NaiveDate) {
+        let today = local_date(now);
+        match self {
+            ReportWindow::Today => (today, today),
+            ReportWindow::Yesterday => {
+                let yesterday = today - Duration::days(1);
+                (yesterday, yesterday)
+            }
+            ReportWindow::Week => {
+                let start = week_start(today);
+                (start, start + Duration::days(6))
+            }
+            ReportWindow::Month => (month_start(today), month_end(today)),
+            ReportWindow::Range { from, to } => (from, to),
+        }
+    }
+}
+
+pub fn report_days(
+    store: &Store,
+    from: NaiveDate,
+    to: NaiveDate,
+    now: DateTime<Utc>,
+    group: ReportGroup,
+) -> Vec<ReportDay> {
+    let mut days = Vec::new();
+    let mut date = from;
+    while date <= to {
+        let next = date + Duration::days(1);
+        let entries = report_window(
+            store,
+            local_day_start(date),
+            local_day_start(next),
+            now,
+            group,
+        );
+        if !entries.is_empty() {
+            days.push(ReportDay { date, entries });
+        }
+        date = next;
+    }
+    days
+}
+
+/// One row per task for a single day, so totals count a task once even
+/// when the grouped rows list it under several tags.
+pub fn day_task_entries(store: &Store, date: NaiveDate, now: DateTime<Utc>) -> Vec<ReportEntry> {
+    report_window(
+        store,
+        local_day_start(date),
+        local_day_start(date + Duration::days(1)),
+        now,
+        ReportGroup::Task,
+    )
+}
 
+pub fn report_window(
+    store: &Store,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    now: DateTime<Utc>,
+    group: ReportGroup,
+) -> Vec<ReportEntry> {
     let mut entries = Vec::new();
 
     for task in &store.tasks {
@@ -25,7 +101,7 @@ pub fn report_today(store: &Store, now: DateTime<Utc>) -> Vec<ReportEntry> {
         let mut latest: Option<DateTime<Utc>> = None;
 
         for segment in &task.segments {
-            let Some((start, end)) = overlap_window(segment, start_utc, end_utc, now) else {
+            let Some((start, end)) = overlap_window(segment, window_start, window_end, now) else {
                 continue;
             };
             let duration = (end - start).num_seconds().max(0);
@@ -54,12 +130,30 @@ pub fn report_today(store: &Store, now: DateTime<Utc>) -> Vec<ReportEntry> {
             continue;
         };
 
-        entries.push(ReportEntry {
-            name: task.name.clone(),
-            start_at,
-            end_at,
-            seconds,
-        });
+        let amount = billable_amount(seconds, task_hourly_rate(store, task));
+        let labels: Vec<String> = match group {
+            ReportGroup::Task => {
+                entries.push(ReportEntry {
+                    name: task.name.clone(),
+                    start_at,
+                    end_at,
+                    seconds,
+                    amount,
+                });
+                continue;
+            }
+            ReportGroup::Tag if task.tags.is_empty() => vec![UNTAGGED_LABEL.to_string()],
+            ReportGroup::Tag => task.tags.iter().map(|tag| format!("+{}", tag)).collect(),
+            ReportGroup::Project => {
+                vec![task_project_name(store, task).unwrap_or_else(|| NO_PROJECT_LABEL.to_string())]
+            }
+            ReportGroup::Client => {
+                vec![task_client_name(store, task).unwrap_or_else(|| NO_CLIENT_LABEL.to_string())]
+            }
+        };
+        for label in labels {
+            add_to_group(&mut entries, label, start_at, end_at, seconds, amount);
+        }
     }
 
     entries.sort_by(|a, b| {
@@ -71,6 +165,33 @@ pub fn report_today(store: &Store, now: DateTime<Utc>) -> Vec<ReportEntry> {
     entries
 }
 
+fn add_to_group(
+    entries: &mut Vec<ReportEntry>,
+    name: String,
+    start_at: DateTime<Utc>,
+    end_at: DateTime<Utc>,
+    seconds: i64,
+    amount: Option<f64>,
+) {
+    if let Some(entry) = entries.iter_mut().find(|entry| entry.name == name) {
+        entry.start_at = entry.start_at.min(start_at);
+        entry.end_at = entry.end_at.max(end_at);
+        entry.seconds += seconds;
+        entry.amount = match (entry.amount, amount) {
+            (Some(total), Some(value)) => Some(total + value),
+            (total, value) => total.or(value),
+        };
+        return;
+    }
+    entries.push(ReportEntry {
+        name,
+        start_at,
+        end_at,
+        seconds,
+        amount,
+    });
+}
+
 pub fn overlap_window(
     segment: &Segment,
     window_start: DateTime<Utc>,
@@ -118,4 +239,118 @@ mod tests {
         assert_eq!(result.0, window_start);
         assert_eq!(result.1, seg_end);
     }
+
+    #[test]
+    fn report_days_splits_segments_at_midnight() {
+        let day1 = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let day2 = day1 + Duration::days(1);
+        let start = local_day_start(day2) - Duration::hours(1);
+        let end = local_day_start(day2) + Duration::hours(2);
+        let store = Store {
+            tasks: vec![crate::model::Task {
+                id: "task".into(),
+                name: "Late shift".into(),
+                tags: Vec::new(),
+                project_id: None,
+                archived: false,
+                created_at: start,
+                closed_at: Some(end),
+                segments: vec![Segment {
+                    start_at: start,
+                    end_at: Some(end),
+                }],
+            }],
+            ..Store::default()
+        };
+
+        let days = report_days(
+            &store,
+            day1,
+            day2 + Duration::days(1),
+            end,
+            ReportGroup::Task,
+        );
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].date, day1);
+        assert_eq!(days[0].entries[0].seconds, 3600);
+        assert_eq!(days[1].date, day2);
+        assert_eq!(days[1].entries[0].seconds, 7200);
+    }
+
+    #[test]
+    fn report_window_sums_time_per_tag() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let task = |id: &str, tags: &[&str], minutes: i64| crate::model::Task {
+            id: id.into(),
+            name: id.into(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            project_id: None,
+            archived: false,
+            created_at: start,
+            closed_at: None,
+            segments: vec![Segment {
+                start_at: start,
+                end_at: Some(start + Duration::minutes(minutes)),
+            }],
+        };
+        let store = Store {
+            tasks: vec![
+                task("a", &["acme", "review"], 30),
+                task("b", &["acme"], 60),
+                task("c", &[], 15),
+            ],
+            ..Store::default()
+        };
+
+        let end = start + Duration::hours(2);
+        let entries = report_window(&store, start, end, end, ReportGroup::Tag);
+        let seconds = |name: &str| {
+            entries
+                .iter()
+                .find(|entry| entry.name == name)
+                .map(|entry| entry.seconds)
+        };
+        assert_eq!(seconds("+acme"), Some(5400));
+        assert_eq!(seconds("+review"), Some(1800));
+        assert_eq!(seconds("(untagged)"), Some(900));
+    }
+
+    #[test]
+    fn day_task_entries_count_multi_tag_task_once() {
+        let start = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let store = Store {
+            tasks: vec![crate::model::Task {
+                id: "a".into(),
+                name: "a".into(),
+                tags: vec!["acme".into(), "code".into()],
+                project_id: Some("p".into()),
+                archived: false,
+                created_at: start,
+                closed_at: None,
+                segments: vec![Segment {
+                    start_at: start,
+                    end_at: Some(start + Duration::hours(1)),
+                }],
+            }],
+            projects: vec![crate::model::Project {
+                id: "p".into(),
+                name: "Site".into(),
+                client_id: None,
+                hourly_rate: Some(100.0),
+                archived: false,
+            }],
+            ..Store::default()
+        };
+
+        let now = start + Duration::hours(2);
+        let date = local_date(start);
+        let days = report_days(&store, date, date, now, ReportGroup::Tag);
+        let grouped: i64 = days[0].entries.iter().map(|entry| entry.seconds).sum();
+        assert_eq!(grouped, 7200);
+        let rows = day_task_entries(&store, date, now);
+        let total: i64 = rows.iter().map(|entry| entry.seconds).sum();
+        assert_eq!(total, 3600);
+        let amounts: Vec<_> = rows.iter().map(|entry| entry.amount).collect();
+        assert_eq!(amounts, vec![Some(100.0)]);
+    }
 }
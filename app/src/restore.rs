@@ -0,0 +1,250 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use crate::model::{Segment, Store, Task};
+use crate::tasks::total_elapsed;
+use crate::time::format_duration;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed {
+        renamed_from: Option<String>,
+        segments_added: usize,
+        segments_removed: usize,
+    },
+}
+
+#[derive(Debug)]
+pub struct TaskChange {
+    pub id: String,
+    pub name: String,
+    pub kind: ChangeKind,
+    pub seconds_delta: i64,
+}
+
+/// What restoring `restored` over `current` would change, task by task.
+pub struct StoreDiff {
+    pub changes: Vec<TaskChange>,
+    pub seconds_delta: i64,
+}
+
+pub fn diff_stores(current: &Store, restored: &Store, now: DateTime<Utc>) -> StoreDiff {
+    let mut changes = Vec::new();
+
+    for task in &restored.tasks {
+        let change = match find(current, &task.id) {
+            None => Some(ChangeKind::Added),
+            Some(before) => {
+                let segments_added = count_missing(&task.segments, &before.segments);
+                let segments_removed = count_missing(&before.segments, &task.segments);
+                let renamed_from = (before.name != task.name).then(|| before.name.clone());
+                (segments_added + segments_removed > 0 || renamed_from.is_some()).then_some(
+                    ChangeKind::Changed {
+                        renamed_from,
+                        segments_added,
+                        segments_removed,
+                    },
+                )
+            }
+        };
+        if let Some(kind) = change {
+            let before = find(current, &task.id).map_or(0, |before| total_elapsed(before, now));
+            changes.push(TaskChange {
+                id: task.id.clone(),
+                name: task.name.clone(),
+                kind,
+                seconds_delta: total_elapsed(task, now) - before,
+            });
+        }
+    }
+    for task in &current.tasks {
+        if find(restored, &task.id).is_none() {
+            changes.push(TaskChange {
+                id: task.id.clone(),
+                name: task.name.clone(),
+                kind: ChangeKind::Removed,
+                seconds_delta: -total_elapsed(task, now),
+            });
+        }
+    }
+
+    let total = |store: &Store| -> i64 {
+        store
+            .tasks
+            .iter()
+            .map(|task| total_elapsed(task, now))
+            .sum()
+    };
+    StoreDiff {
+        changes,
+        seconds_delta: total(restored) - total(current),
+    }
+}
+
+fn find<'a>(store: &'a Store, id: &str) -> Option<&'a Task> {
+    store.tasks.iter().find(|task| task.id == id)
+}
+
+fn count_missing(segments: &[Segment], other: &[Segment]) -> usize {
+    segments
+        .iter()
+        .filter(|segment| {
+            !other
+                .iter()
+                .any(|o| o.start_at == segment.start_at && o.end_at == segment.end_at)
+        })
+        .count()
+}
+
+/// Moves the named tasks from a backup into `current`, replacing tasks with the same id, and
+/// brings along projects and clients they reference that `current` lacks.
+pub fn restore_tasks(current: &mut Store, mut backup: Store, ids: &[String]) -> Result<(), String> {
+    let mut selected = Vec::new();
+    for id in ids {
+        if selected.iter().any(|task: &Task| task.id == *id) {
+            continue;
+        }
+        let idx = backup
+            .tasks
+            .iter()
+            .position(|task| task.id == *id)
+            .ok_or_else(|| format!("No task with id \"{}\" in the backup.", id))?;
+        selected.push(backup.tasks.swap_remove(idx));
+    }
+
+    for task in selected {
+        if let Some(project_id) = &task.project_id
+            && !current
+                .projects
+                .iter()
+                .any(|project| project.id == *project_id)
+            && let Some(idx) = backup.projects.iter().position(|p| p.id == *project_id)
+        {
+            let project = backup.projects.swap_remove(idx);
+            if let Some(client_id) = &project.client_id
+                && !current.clients.iter().any(|client| client.id == *client_id)
+                && let Some(idx) = backup.clients.iter().position(|c| c.id == *client_id)
+            {
+                current.clients.push(backup.clients.swap_remove(idx));
+            }
+            current.projects.push(project);
+        }
+        match current
+            .tasks
+            .iter_mut()
+            .find(|existing| existing.id == task.id)
+        {
+            Some(existing) => *existing = task,
+            None => current.tasks.push(task),
+        }
+    }
+    Ok(())
+}
+
+pub fn format_signed_duration(seconds: i64) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    format!("{}{}", sign, format_duration(seconds.abs()))
+}
+
+impl fmt::Display for TaskChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let delta = format_signed_duration(self.seconds_delta);
+        match &self.kind {
+            ChangeKind::Added => write!(f, "+ {} ({}) {}", self.name, self.id, delta),
+            ChangeKind::Removed => write!(f, "- {} ({}) {}", self.name, self.id, delta),
+            ChangeKind::Changed {
+                renamed_from,
+                segments_added,
+                segments_removed,
+            } => {
+                write!(f, "~ {} ({})", self.name, self.id)?;
+                if let Some(old_name) = renamed_from {
+                    write!(f, " renamed from \"{}\",", old_name)?;
+                }
+                write!(
+                    f,
+                    " {} segment(s) restored, {} dropped {}",
+                    segments_added, segments_removed, delta
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::model::Project;
+
+    #[test]
+    fn diff_and_partial_restore_compare_tasks_by_id() {
+        let nine = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let at = |minutes: i64| nine + Duration::minutes(minutes);
+        let task = |id: &str, name: &str, segments: &[(i64, i64)]| Task {
+            id: id.into(),
+            name: name.into(),
+            tags: Vec::new(),
+            project_id: None,
+            archived: false,
+            created_at: nine,
+            closed_at: segments.last().map(|&(_, end)| at(end)),
+            segments: segments
+                .iter()
+                .map(|&(start, end)| Segment {
+                    start_at: at(start),
+                    end_at: Some(at(end)),
+                })
+                .collect(),
+        };
+        let current = Store {
+            tasks: vec![
+                task("a", "Docs", &[(0, 30)]),
+                task("b", "Review", &[(60, 90)]),
+                task("new", "Email", &[(120, 130)]),
+            ],
+            ..Store::default()
+        };
+        let mut linked = task("gone", "Call", &[(200, 260)]);
+        linked.project_id = Some("p".into());
+        let backup = Store {
+            tasks: vec![
+                task("a", "Docs", &[(0, 30)]),
+                task("b", "Code review", &[(45, 50), (60, 90)]),
+                linked,
+            ],
+            projects: vec![Project {
+                id: "p".into(),
+                name: "Website".into(),
+                client_id: None,
+                hourly_rate: None,
+                archived: false,
+            }],
+            ..Store::default()
+        };
+
+        let diff = diff_stores(&current, &backup, at(300));
+        let lines: Vec<String> = diff.changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "~ Code review (b) renamed from \"Review\", 1 segment(s) restored, 0 dropped +00:05:00",
+                "+ Call (gone) +01:00:00",
+                "- Email (new) -00:10:00",
+            ]
+        );
+        assert_eq!(diff.seconds_delta, 55 * 60);
+
+        let mut restored = current;
+        restore_tasks(&mut restored, backup.clone(), &["gone".into(), "b".into()]).unwrap();
+        let ids: Vec<&str> = restored.tasks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "new", "gone"]);
+        assert_eq!(restored.tasks[1].segments.len(), 2);
+        assert_eq!(restored.projects.len(), 1);
+        assert!(restore_tasks(&mut restored, backup, &["missing".into()]).is_err());
+    }
+}
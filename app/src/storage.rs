@@ -1,18 +1,49 @@
 use std::fs;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
 use directories::ProjectDirs;
+use uuid::Uuid;
 
-use crate::crypto::{decrypt_store, encrypt_store};
+use crate::config::BackupConfig;
+use crate::crypto::Unlocker;
+use crate::migrate::migrate_store;
 use crate::model::Store;
 
-const BACKUP_COUNT: usize = 3;
+/// Backups written before timestamped names were `<name>.bak1` to `<name>.bak3`.
+const LEGACY_BACKUP_COUNT: usize = 3;
+const BACKUP_TIME_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// How long a command waits for another ttt process to release the data file.
+pub const LOCK_WAIT: Duration = Duration::from_secs(5);
+const LOCK_RETRY: Duration = Duration::from_millis(100);
 
 pub struct BackupEntry {
     pub path: PathBuf,
-    pub modified: Option<SystemTime>,
-    pub size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct BackupSummary {
+    pub tasks: usize,
+    pub latest_activity: Option<DateTime<Utc>>,
+}
+
+impl BackupSummary {
+    pub fn of(store: &Store) -> Self {
+        BackupSummary {
+            tasks: store.tasks.len(),
+            latest_activity: store
+                .tasks
+                .iter()
+                .flat_map(|task| &task.segments)
+                .flat_map(|segment| [Some(segment.start_at), segment.end_at])
+                .flatten()
+                .max(),
+        }
+    }
 }
 
 pub fn data_file_path(custom: Option<PathBuf>) -> PathBuf {
@@ -27,19 +58,27 @@ pub fn data_file_path(custom: Option<PathBuf>) -> PathBuf {
     PathBuf::from("ttt.json")
 }
 
-pub fn load_store(path: &Path, passphrase: &str) -> Result<Store, String> {
+pub fn load_store(path: &Path, unlocker: &Unlocker) -> Result<Store, String> {
     if !path.exists() {
-        return Ok(Store {
-            version: 1,
-            tasks: Vec::new(),
-        });
+        return Ok(Store::default());
     }
 
     let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
-    decrypt_store(&contents, passphrase)
+    let store = unlocker.open(&contents)?;
+    migrate_store(store, Utc::now())
+}
+
+pub fn save_store(
+    path: &Path,
+    store: &Store,
+    unlocker: &Unlocker,
+    backups: &BackupConfig,
+) -> Result<(), String> {
+    save_payload(path, &unlocker.seal(store)?, backups)
 }
 
-pub fn save_store(path: &Path, store: &Store, passphrase: &str) -> Result<(), String> {
+/// Writes an already sealed envelope, backing up the previous file like `save_store`.
+pub fn save_payload(path: &Path, payload: &str, backups: &BackupConfig) -> Result<(), String> {
     if let Some(parent) = path.parent()
         && !parent.exists()
     {
@@ -47,61 +86,211 @@ pub fn save_store(path: &Path, store: &Store, passphrase: &str) -> Result<(), St
     }
 
     if !is_backup_path(path) {
-        rotate_backups(path)?;
+        create_backup(path, backups, Utc::now())?;
     }
 
-    let payload = encrypt_store(store, passphrase)?;
-    write_secure(path, payload.as_bytes())
+    write_atomic(path, payload.as_bytes())
 }
 
-pub fn list_backups(path: &Path) -> Vec<BackupEntry> {
+/// Advisory lock on `<data file>.lock`, held from load to save and released on drop.
+pub struct DataLock {
+    _file: fs::File,
+}
+
+pub fn lock_data_file(path: &Path, wait: Duration) -> Result<DataLock, String> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+        && !parent.exists()
+    {
+        fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+    }
+    let lock_path = lock_path(path);
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .map_err(|err| format!("Could not open {}: {}", lock_path.display(), err))?;
+
+    let started = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(DataLock { _file: file }),
+            Err(fs::TryLockError::WouldBlock) if started.elapsed() < wait => {
+                thread::sleep(LOCK_RETRY);
+            }
+            Err(fs::TryLockError::WouldBlock) => {
+                return Err(format!(
+                    "Another ttt process is using {} (waited {}s). Try again once it finishes.",
+                    path.display(),
+                    wait.as_secs()
+                ));
+            }
+            Err(fs::TryLockError::Error(err)) => {
+                return Err(format!("Could not lock {}: {}", lock_path.display(), err));
+            }
+        }
+    }
+}
+
+/// The data file's contents when it was loaded, compared again right before saving so that
+/// writes from processes that ignore the lock are not silently overwritten.
+pub struct Snapshot(Option<Vec<u8>>);
+
+impl Snapshot {
+    pub fn take(path: &Path) -> Result<Self, String> {
+        read_if_exists(path).map(Snapshot)
+    }
+
+    /// Whether the file did not exist when the snapshot was taken.
+    pub fn is_missing(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn ensure_unchanged(&self, path: &Path) -> Result<(), String> {
+        if read_if_exists(path)? != self.0 {
+            return Err(format!(
+                "{} changed since it was read. Nothing was saved; run the command again.",
+                path.display()
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("Could not read {}: {}", path.display(), err)),
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let name = file_name(path);
+    path.with_file_name(format!("{}.lock", name))
+}
+
+/// All backups of the data file, newest first, including legacy `.bakN` files.
+pub fn list_backups(path: &Path, config: &BackupConfig) -> Vec<BackupEntry> {
+    let name = file_name(path);
     let mut entries = Vec::new();
-    for index in 1..=BACKUP_COUNT {
-        let backup_path = backup_path(path, index);
-        if let Ok(metadata) = fs::metadata(&backup_path) {
+    let dir = backup_dir(path, config);
+    for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+        let file_name = entry.file_name();
+        let Some(created_at) = file_name
+            .to_str()
+            .and_then(|value| backup_time(name, value))
+        else {
+            continue;
+        };
+        entries.push(BackupEntry {
+            path: entry.path(),
+            created_at,
+        });
+    }
+    for index in 1..=LEGACY_BACKUP_COUNT {
+        let legacy = path.with_file_name(format!("{}.bak{}", name, index));
+        if let Ok(metadata) = fs::metadata(&legacy) {
             entries.push(BackupEntry {
-                path: backup_path,
-                modified: metadata.modified().ok(),
-                size: metadata.len(),
+                path: legacy,
+                created_at: metadata
+                    .modified()
+                    .map(DateTime::from)
+                    .unwrap_or(DateTime::UNIX_EPOCH),
             });
         }
     }
+    entries.sort_by(|a, b| b.created_at.cmp(&a.created_at));
     entries
 }
 
-fn rotate_backups(path: &Path) -> Result<(), String> {
+/// Copies the current data file to a timestamped backup and prunes backups the retention
+/// policy no longer needs.
+fn create_backup(path: &Path, config: &BackupConfig, now: DateTime<Utc>) -> Result<(), String> {
     if !path.exists() {
         return Ok(());
     }
-
-    let oldest = backup_path(path, BACKUP_COUNT);
-    if oldest.exists() {
-        fs::remove_file(&oldest).map_err(|err| err.to_string())?;
+    let dir = backup_dir(path, config);
+    if !dir.exists() {
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
     }
+    let contents = fs::read(path).map_err(|err| err.to_string())?;
+    let backup = dir.join(format!(
+        "{}.{}.bak",
+        file_name(path),
+        now.format(BACKUP_TIME_FORMAT)
+    ));
+    write_atomic(&backup, &contents)?;
 
-    for index in (1..BACKUP_COUNT).rev() {
-        let src = backup_path(path, index);
-        if src.exists() {
-            let dest = backup_path(path, index + 1);
-            fs::rename(&src, &dest).map_err(|err| err.to_string())?;
+    let backups = list_backups(path, config);
+    let times: Vec<DateTime<Utc>> = backups.iter().map(|entry| entry.created_at).collect();
+    for (entry, keep) in backups.iter().zip(retained_backups(&times, config, now)) {
+        if !keep {
+            fs::remove_file(&entry.path)
+                .map_err(|err| format!("Could not remove {}: {}", entry.path.display(), err))?;
         }
     }
-
-    let backup = backup_path(path, 1);
-    fs::copy(path, &backup).map_err(|err| err.to_string())?;
-    set_permissions_secure(&backup)?;
     Ok(())
 }
 
-fn backup_path(path: &Path, index: usize) -> PathBuf {
-    let name = path
-        .file_name()
+/// Decides which backups to keep, given their times newest first: the newest `keep_last`,
+/// the newest backup of each hour within `hourly_hours` and of each local day within
+/// `daily_days`. A window too large to represent keeps every backup.
+fn retained_backups(
+    times: &[DateTime<Utc>],
+    config: &BackupConfig,
+    now: DateTime<Utc>,
+) -> Vec<bool> {
+    let hourly = chrono::Duration::try_hours(config.hourly_hours);
+    let daily = chrono::Duration::try_days(config.daily_days);
+    let within = |age, window: Option<chrono::Duration>| window.is_none_or(|window| age <= window);
+    let mut hours = Vec::new();
+    let mut days = Vec::new();
+    times
+        .iter()
+        .enumerate()
+        .map(|(idx, &time)| {
+            let age = now - time;
+            let mut keep = idx < config.keep_last;
+            let hour = time.timestamp().div_euclid(3600);
+            if within(age, hourly) && !hours.contains(&hour) {
+                hours.push(hour);
+                keep = true;
+            }
+            let day = time.with_timezone(&Local).date_naive();
+            if within(age, daily) && !days.contains(&day) {
+                days.push(day);
+                keep = true;
+            }
+            keep
+        })
+        .collect()
+}
+
+fn backup_dir(path: &Path, config: &BackupConfig) -> PathBuf {
+    match (&config.dir, path.parent()) {
+        (Some(dir), _) => dir.clone(),
+        (None, Some(parent)) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        (None, _) => PathBuf::from("."),
+    }
+}
+
+fn backup_time(data_name: &str, backup_name: &str) -> Option<DateTime<Utc>> {
+    let stamp = backup_name
+        .strip_prefix(data_name)?
+        .strip_prefix('.')?
+        .strip_suffix(".bak")?;
+    NaiveDateTime::parse_from_str(stamp, BACKUP_TIME_FORMAT)
+        .ok()
+        .map(|naive| naive.and_utc())
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
         .and_then(|value| value.to_str())
-        .unwrap_or("ttt.json");
-    let backup_name = format!("{}.bak{}", name, index);
-    let mut backup_path = path.to_path_buf();
-    backup_path.set_file_name(backup_name);
-    backup_path
+        .unwrap_or("ttt.json")
 }
 
 fn is_backup_path(path: &Path) -> bool {
@@ -111,35 +300,233 @@ fn is_backup_path(path: &Path) -> bool {
         .unwrap_or(false)
 }
 
-fn write_secure(path: &Path, payload: &[u8]) -> Result<(), String> {
+/// Replaces `path` without ever leaving a partially written file behind: the payload goes to an
+/// owner-only temp file in the same directory, which is synced and then renamed over the target.
+fn write_atomic(path: &Path, payload: &[u8]) -> Result<(), String> {
+    write_atomic_with(path, payload, |file, payload| file.write_all(payload))
+}
+
+fn write_atomic_with<F>(path: &Path, payload: &[u8], write: F) -> Result<(), String>
+where
+    F: FnOnce(&mut fs::File, &[u8]) -> io::Result<()>,
+{
+    let temp = temp_path(path);
+    let result = write_temp(&temp, payload, write).and_then(|()| {
+        fs::rename(&temp, path)
+            .map_err(|err| format!("Could not replace {}: {}", path.display(), err))
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        return result;
+    }
+    sync_parent_dir(path)
+}
+
+fn write_temp<F>(temp: &Path, payload: &[u8], write: F) -> Result<(), String>
+where
+    F: FnOnce(&mut fs::File, &[u8]) -> io::Result<()>,
+{
+    let error = |err: io::Error| format!("Could not write {}: {}", temp.display(), err);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
     #[cfg(unix)]
     {
         use std::os::unix::fs::OpenOptionsExt;
-        let mut file = fs::OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .mode(0o600)
-            .open(path)
-            .map_err(|err| err.to_string())?;
-        use std::io::Write;
-        file.write_all(payload).map_err(|err| err.to_string())?;
-        set_permissions_secure(path)?;
-        Ok(())
+        options.mode(0o600);
     }
+    let mut file = options.open(temp).map_err(error)?;
+    write(&mut file, payload).map_err(error)?;
+    file.sync_all().map_err(error)
+}
 
-    #[cfg(not(unix))]
-    {
-        fs::write(path, payload).map_err(|err| err.to_string())
-    }
+fn temp_path(path: &Path) -> PathBuf {
+    let name = file_name(path);
+    path.with_file_name(format!(".{}.{}.tmp", name, Uuid::new_v4().simple()))
 }
 
-fn set_permissions_secure(path: &Path) -> Result<(), String> {
+fn sync_parent_dir(path: &Path) -> Result<(), String> {
+    // The rename is only durable once the directory entry itself reaches the disk.
     #[cfg(unix)]
     {
-        use std::os::unix::fs::PermissionsExt;
-        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
-            .map_err(|err| err.to_string())?;
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::File::open(parent)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|err| format!("Could not sync {}: {}", parent.display(), err))?;
     }
+    #[cfg(not(unix))]
+    let _ = path;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("ttt-test-{}", Uuid::new_v4().simple()));
+            fs::create_dir_all(&dir).unwrap();
+            TestDir(dir)
+        }
+
+        fn entries(&self) -> Vec<String> {
+            let mut names: Vec<String> = fs::read_dir(&self.0)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn atomic_write_replaces_the_file_with_owner_only_permissions() {
+        let dir = TestDir::new();
+        let path = dir.0.join("ttt.json");
+        fs::write(&path, "old").unwrap();
+
+        write_atomic(&path, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(dir.entries(), vec!["ttt.json"]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn failed_write_keeps_the_original_and_removes_the_temp_file() {
+        let dir = TestDir::new();
+        let path = dir.0.join("ttt.json");
+        fs::write(&path, "old").unwrap();
+
+        let err = write_atomic_with(&path, b"new contents", |file, payload| {
+            file.write_all(&payload[..3])?;
+            Err(io::Error::other("No space left on device"))
+        })
+        .unwrap_err();
+
+        assert!(err.contains("No space left on device"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert_eq!(dir.entries(), vec!["ttt.json"]);
+    }
+
+    #[test]
+    fn failed_rename_removes_the_temp_file() {
+        let dir = TestDir::new();
+        // A non-empty directory cannot be replaced by a file.
+        let path = dir.0.join("ttt.json");
+        fs::create_dir_all(path.join("child")).unwrap();
+
+        let err = write_atomic(&path, b"new").unwrap_err();
+
+        assert!(err.starts_with("Could not replace"));
+        assert!(path.join("child").is_dir());
+        assert_eq!(dir.entries(), vec!["ttt.json"]);
+    }
+
+    #[test]
+    fn second_lock_times_out_and_snapshot_detects_foreign_writes() {
+        let dir = TestDir::new();
+        let path = dir.0.join("ttt.json");
+
+        let lock = lock_data_file(&path, LOCK_WAIT).unwrap();
+        let err = lock_data_file(&path, Duration::from_millis(250))
+            .err()
+            .unwrap();
+        assert!(err.starts_with("Another ttt process is using"));
+        drop(lock);
+        let _lock = lock_data_file(&path, Duration::ZERO).unwrap();
+
+        let snapshot = Snapshot::take(&path).unwrap();
+        fs::write(&path, "written elsewhere").unwrap();
+        assert!(snapshot.ensure_unchanged(&path).is_err());
+        let snapshot = Snapshot::take(&path).unwrap();
+        assert!(snapshot.ensure_unchanged(&path).is_ok());
+    }
+
+    #[test]
+    fn backups_are_timestamped_and_pruned_by_the_retention_policy() {
+        use chrono::TimeZone;
+
+        let now = Utc.with_ymd_and_hms(2026, 3, 10, 12, 30, 0).unwrap();
+        let ago = |minutes: i64| now - chrono::Duration::minutes(minutes);
+        let config = BackupConfig {
+            keep_last: 2,
+            ..BackupConfig::default()
+        };
+        let day = 24 * 60;
+        let times = [
+            ago(1),
+            ago(2),
+            ago(3),
+            ago(120),
+            ago(130),
+            ago(3 * day),
+            ago(3 * day + 10),
+            ago(40 * day),
+        ];
+        assert_eq!(
+            retained_backups(&times, &config, now),
+            vec![true, true, false, true, false, true, false, false]
+        );
+        let unbounded = BackupConfig {
+            keep_last: 0,
+            hourly_hours: i64::MAX,
+            daily_days: i64::MAX,
+            ..BackupConfig::default()
+        };
+        assert_eq!(
+            retained_backups(&times, &unbounded, now),
+            vec![true, false, false, true, false, true, false, true]
+        );
+
+        let dir = TestDir::new();
+        let path = dir.0.join("ttt.json");
+        let config = BackupConfig {
+            dir: Some(dir.0.join("backups")),
+            ..BackupConfig::default()
+        };
+        create_backup(&path, &config, ago(5)).unwrap();
+        assert!(list_backups(&path, &config).is_empty());
+        fs::write(&path, "first").unwrap();
+        create_backup(&path, &config, ago(5)).unwrap();
+        fs::write(&path, "second").unwrap();
+        create_backup(&path, &config, now).unwrap();
+
+        let backups = list_backups(&path, &config);
+        let times: Vec<_> = backups.iter().map(|entry| entry.created_at).collect();
+        assert_eq!(times, vec![now, ago(5)]);
+        assert_eq!(fs::read_to_string(&backups[1].path).unwrap(), "first");
+        assert!(
+            backups[0]
+                .path
+                .ends_with("backups/ttt.json.20260310T123000.000Z.bak")
+        );
+    }
+
+    #[test]
+    fn missing_directory_is_reported_without_side_effects() {
+        let dir = TestDir::new();
+        let path = dir.0.join("missing").join("ttt.json");
+
+        let err = write_atomic(&path, b"new").unwrap_err();
+
+        assert!(err.starts_with("Could not write"));
+        assert!(dir.entries().is_empty());
+    }
+}
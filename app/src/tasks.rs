@@ -2,6 +2,7 @@ use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 use crate::model::{Segment, Store, Task, TaskState};
+use crate::time::format_datetime_local;
 
 pub fn current_task_state(store: &Store) -> Option<(usize, TaskState)> {
     for (idx, task) in store.tasks.iter().enumerate() {
@@ -31,10 +32,90 @@ pub fn active_task_name(store: &Store) -> Option<String> {
         .map(|idx| store.tasks[idx].name.clone())
 }
 
-pub fn start_task(store: &mut Store, name: String, now: DateTime<Utc>) {
+pub fn parse_task_args(args: &[String]) -> Result<(String, Vec<String>), String> {
+    let mut words = Vec::new();
+    let mut tags: Vec<String> = Vec::new();
+    for arg in args {
+        if let Some(tag) = arg.strip_prefix('+') {
+            let tag = normalize_tag(tag)?;
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        } else {
+            words.push(arg.trim());
+        }
+    }
+    let name = words
+        .into_iter()
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok((name, tags))
+}
+
+pub fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().trim_start_matches('+').to_lowercase();
+    if tag.is_empty() || tag.chars().any(char::is_whitespace) {
+        return Err(format!(
+            "Invalid tag \"{}\". Tags are single words like +acme.",
+            tag
+        ));
+    }
+    Ok(tag)
+}
+
+pub fn normalize_task_name(name: &str) -> String {
+    name.split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Finds a stopped, unarchived task by id or name (case and spacing ignored); without a query
+/// the most recently stopped task. An id also matches archived tasks.
+pub fn find_task_to_continue(store: &Store, query: Option<&str>) -> Result<usize, String> {
+    let most_recent = |name: Option<&str>| {
+        store
+            .tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| task.closed_at.is_some() && !task.archived)
+            .filter(|(_, task)| name.is_none_or(|name| normalize_task_name(&task.name) == name))
+            .max_by_key(|(_, task)| task.closed_at)
+            .map(|(idx, _)| idx)
+    };
+    match query {
+        None => most_recent(None).ok_or_else(|| "No stopped task to continue.".to_string()),
+        Some(query) => {
+            if let Some(idx) = store.tasks.iter().position(|task| task.id == query) {
+                return Ok(idx);
+            }
+            most_recent(Some(&normalize_task_name(query)))
+                .ok_or_else(|| format!("No stopped task named \"{}\".", query))
+        }
+    }
+}
+
+pub fn continue_task(store: &mut Store, idx: usize, now: DateTime<Utc>) {
+    let task = &mut store.tasks[idx];
+    task.closed_at = None;
+    task.archived = false;
+    resume_task(store, idx, now);
+}
+
+pub fn start_task(
+    store: &mut Store,
+    name: String,
+    tags: Vec<String>,
+    project_id: Option<String>,
+    now: DateTime<Utc>,
+) {
     let task = Task {
         id: Uuid::new_v4().to_string(),
         name,
+        tags,
+        project_id,
+        archived: false,
         created_at: now,
         closed_at: None,
         segments: vec![Segment {
@@ -68,6 +149,52 @@ pub fn resume_task(store: &mut Store, idx: usize, now: DateTime<Utc>) {
     });
 }
 
+pub fn validate_close_time(task: &Task, at: DateTime<Utc>) -> Result<(), String> {
+    let latest = task
+        .segments
+        .iter()
+        .flat_map(|segment| [Some(segment.start_at), segment.end_at])
+        .flatten()
+        .max();
+    match latest {
+        Some(latest) if at < latest => Err(format!(
+            "{} is before the last recorded time of \"{}\" ({}).",
+            format_datetime_local(at),
+            task.name,
+            format_datetime_local(latest)
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub fn validate_open_time(
+    store: &Store,
+    idx: Option<usize>,
+    at: DateTime<Utc>,
+) -> Result<(), String> {
+    if let Some(idx) = idx {
+        validate_close_time(&store.tasks[idx], at)?;
+    }
+    for (other_idx, task) in store.tasks.iter().enumerate() {
+        if Some(other_idx) == idx {
+            continue;
+        }
+        let overlaps = task
+            .segments
+            .iter()
+            .any(|segment| segment.end_at.is_none_or(|end_at| end_at > at));
+        if overlaps {
+            return Err(format!(
+                "{} overlaps time already recorded for \"{}\" ({}).",
+                format_datetime_local(at),
+                task.name,
+                task.id
+            ));
+        }
+    }
+    Ok(())
+}
+
 pub fn total_elapsed(task: &Task, now: DateTime<Utc>) -> i64 {
     task.segments
         .iter()
@@ -103,6 +230,9 @@ mod tests {
         let active = Task {
             id: "active".into(),
             name: "Active".into(),
+            tags: Vec::new(),
+            project_id: None,
+            archived: false,
             created_at: now,
             closed_at: None,
             segments: vec![Segment {
@@ -113,6 +243,9 @@ mod tests {
         let paused = Task {
             id: "paused".into(),
             name: "Paused".into(),
+            tags: Vec::new(),
+            project_id: None,
+            archived: false,
             created_at: now,
             closed_at: None,
             segments: vec![Segment {
@@ -122,15 +255,15 @@ mod tests {
         };
 
         let store = Store {
-            version: 1,
             tasks: vec![active],
+            ..Store::default()
         };
         let state = current_task_state(&store);
         assert_eq!(state, Some((0, TaskState::Active)));
 
         let store = Store {
-            version: 1,
             tasks: vec![paused],
+            ..Store::default()
         };
         let state = current_task_state(&store);
         assert_eq!(state, Some((0, TaskState::Paused)));
@@ -143,6 +276,9 @@ mod tests {
         let task = Task {
             id: "task".into(),
             name: "Task".into(),
+            tags: Vec::new(),
+            project_id: None,
+            archived: false,
             created_at: start,
             closed_at: None,
             segments: vec![Segment {
@@ -152,4 +288,89 @@ mod tests {
         };
         assert_eq!(total_elapsed(&task, now), 1800);
     }
+
+    #[test]
+    fn open_time_cannot_overlap_other_tasks_or_precede_own_segments() {
+        let nine = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let ten = Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap();
+        let task = |id: &str, start, end| Task {
+            id: id.into(),
+            name: id.into(),
+            tags: Vec::new(),
+            project_id: None,
+            archived: false,
+            created_at: start,
+            closed_at: None,
+            segments: vec![Segment {
+                start_at: start,
+                end_at: Some(end),
+            }],
+        };
+        let store = Store {
+            tasks: vec![task("a", nine, ten), task("b", nine, nine)],
+            ..Store::default()
+        };
+
+        assert!(validate_open_time(&store, None, ten).is_ok());
+        assert!(validate_open_time(&store, None, ten - chrono::Duration::minutes(5)).is_err());
+        assert!(validate_open_time(&store, Some(0), ten - chrono::Duration::minutes(5)).is_err());
+        assert!(validate_close_time(&store.tasks[0], nine).is_err());
+    }
+
+    #[test]
+    fn continue_reopens_the_latest_stopped_task_with_that_name() {
+        let nine = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let task = |id: &str, name: &str, hour: i64| Task {
+            id: id.into(),
+            name: name.into(),
+            tags: Vec::new(),
+            project_id: None,
+            archived: false,
+            created_at: nine,
+            closed_at: Some(nine + chrono::Duration::hours(hour)),
+            segments: vec![Segment {
+                start_at: nine,
+                end_at: Some(nine + chrono::Duration::hours(hour)),
+            }],
+        };
+        let mut store = Store {
+            tasks: vec![
+                task("a", "Code review", 3),
+                task("b", "code  review", 1),
+                task("c", "Docs", 2),
+            ],
+            ..Store::default()
+        };
+
+        assert_eq!(find_task_to_continue(&store, None), Ok(0));
+        assert_eq!(find_task_to_continue(&store, Some("CODE REVIEW")), Ok(0));
+        assert_eq!(find_task_to_continue(&store, Some("b")), Ok(1));
+        assert!(find_task_to_continue(&store, Some("missing")).is_err());
+
+        let later = nine + chrono::Duration::hours(4);
+        continue_task(&mut store, 2, later);
+        assert_eq!(store.tasks[2].closed_at, None);
+        assert_eq!(store.tasks[2].segments.len(), 2);
+        assert_eq!(current_task_state(&store), Some((2, TaskState::Active)));
+    }
+
+    #[test]
+    fn parse_task_args_splits_name_and_tags() {
+        let args: Vec<String> = [
+            "Review",
+            "+ACME",
+            "pull",
+            "request",
+            "+code-review",
+            "+acme",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        let (name, tags) = parse_task_args(&args).unwrap();
+        assert_eq!(name, "Review pull request");
+        assert_eq!(tags, vec!["acme".to_string(), "code-review".to_string()]);
+
+        assert!(parse_task_args(&["+".to_string()]).is_err());
+    }
 }
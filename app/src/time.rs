@@ -1,4 +1,7 @@
-use chrono::{DateTime, Local, Utc};
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+    Weekday,
+};
 
 pub fn format_duration(seconds: i64) -> String {
     let total = seconds.max(0);
@@ -16,6 +19,240 @@ pub fn format_time_local_display(dt: DateTime<Utc>) -> String {
     dt.with_timezone(&Local).format("%H:%M:%S").to_string()
 }
 
+pub fn local_date(dt: DateTime<Utc>) -> NaiveDate {
+    dt.with_timezone(&Local).date_naive()
+}
+
+pub fn local_day_start(date: NaiveDate) -> DateTime<Utc> {
+    let start_local = date.and_hms_opt(0, 0, 0).unwrap();
+    // Midnight can fall into a DST gap; the day then starts an hour later.
+    Local
+        .from_local_datetime(&start_local)
+        .earliest()
+        .or_else(|| {
+            Local
+                .from_local_datetime(&(start_local + Duration::hours(1)))
+                .earliest()
+        })
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+pub fn local_to_utc(naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap()
+}
+
+pub fn month_end(date: NaiveDate) -> NaiveDate {
+    let (year, month) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+    NaiveDate::from_ymd_opt(year, month, 1).unwrap() - Duration::days(1)
+}
+
+pub fn parse_datetime_input(
+    input: &str,
+    now: DateTime<Utc>,
+    label: &str,
+) -> Result<DateTime<Utc>, String> {
+    parse_time_expression(input, now).ok_or_else(|| {
+        format!(
+            "Invalid {} time \"{}\". Use e.g. now, 14:30, 2026-10-15 09:00, -20m, 2h ago, \
+             yesterday 17:00, monday 9am or RFC3339.",
+            label,
+            input.trim()
+        )
+    })
+}
+
+fn parse_time_expression(input: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+    let lower = input.to_ascii_lowercase();
+    if lower == "now" {
+        return Some(now);
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    if let Some(offset) = lower.strip_prefix('-') {
+        return parse_offset(offset).and_then(|offset| now.checked_sub_signed(offset));
+    }
+    if let Some(offset) = lower.strip_prefix('+') {
+        return parse_offset(offset).and_then(|offset| now.checked_add_signed(offset));
+    }
+    if let Some(offset) = lower.strip_suffix("ago") {
+        return parse_offset(offset).and_then(|offset| now.checked_sub_signed(offset));
+    }
+
+    // Everything else is local wall-clock time: an optional day, then an optional time of day.
+    let today = local_date(now);
+    let normalized = split_iso_date_time(&lower);
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    let (date, rest) = match words.as_slice() {
+        ["last", day, rest @ ..] => (
+            previous_weekday(today, day.parse::<Weekday>().ok()?, false),
+            rest,
+        ),
+        [day, rest @ ..] => match parse_day_word(day, today) {
+            Some(date) => (date, rest),
+            None => (today, words.as_slice()),
+        },
+        [] => return None,
+    };
+    let time = if rest.is_empty() {
+        NaiveTime::MIN
+    } else {
+        parse_clock(&rest.concat())?
+    };
+    local_to_utc(date.and_time(time))
+}
+
+fn parse_offset(input: &str) -> Option<Duration> {
+    let compact: String = input.split_whitespace().collect();
+    parse_duration_input(&compact).ok()
+}
+
+fn split_iso_date_time(input: &str) -> String {
+    // "2026-10-15t09:00" (lowercased) reads the same as "2026-10-15 09:00".
+    match input.split_once('t') {
+        Some((date, time)) if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok() => {
+            format!("{} {}", date, time)
+        }
+        _ => input.to_string(),
+    }
+}
+
+fn parse_day_word(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match word {
+        "today" => Some(today),
+        "yesterday" => Some(today - Duration::days(1)),
+        "tomorrow" => Some(today + Duration::days(1)),
+        _ => NaiveDate::parse_from_str(word, "%Y-%m-%d")
+            .ok()
+            .or_else(|| Some(previous_weekday(today, word.parse().ok()?, true))),
+    }
+}
+
+fn previous_weekday(today: NaiveDate, weekday: Weekday, include_today: bool) -> NaiveDate {
+    let mut days_back =
+        (7 + today.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+    if days_back == 0 && !include_today {
+        days_back = 7;
+    }
+    today - Duration::days(days_back as i64)
+}
+
+fn parse_clock(input: &str) -> Option<NaiveTime> {
+    let (clock, meridiem) = if let Some(clock) = input.strip_suffix("am") {
+        (clock, Some(false))
+    } else if let Some(clock) = input.strip_suffix("pm") {
+        (clock, Some(true))
+    } else {
+        (input, None)
+    };
+    let mut parts = clock.split(':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = match parts.next() {
+        Some(minute) => minute.parse().ok()?,
+        // A bare hour is only unambiguous with am/pm.
+        None if meridiem.is_some() => 0,
+        None => return None,
+    };
+    let second: u32 = match parts.next() {
+        Some(second) => second.parse().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    let hour = match meridiem {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(pm) => hour % 12 + if pm { 12 } else { 0 },
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+pub fn resolve_event_time(
+    at: Option<&str>,
+    ago: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>, String> {
+    let at = match (at, ago) {
+        (Some(_), Some(_)) => return Err("Use either --at or --ago, not both.".into()),
+        (Some(at), None) => parse_datetime_input(at, now, "--at")?,
+        (None, Some(ago)) => now
+            .checked_sub_signed(parse_duration_input(ago)?)
+            .ok_or_else(|| format!("--ago {} is too far in the past.", ago.trim()))?,
+        (None, None) => return Ok(now),
+    };
+    if at > now {
+        return Err("The event time cannot be in the future.".into());
+    }
+    Ok(at)
+}
+
+pub fn parse_duration_input(input: &str) -> Result<Duration, String> {
+    let invalid = || {
+        format!(
+            "Invalid duration \"{}\". Use values like 15m, 1h30m or 90s.",
+            input
+        )
+    };
+    let too_long = || format!("Duration \"{}\" is too long.", input.trim());
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    let mut seen_unit = false;
+    for ch in input.trim().chars() {
+        if ch.is_ascii_digit() {
+            digits.push(ch);
+            continue;
+        }
+        let value: i64 = digits.parse().map_err(|_| invalid())?;
+        digits.clear();
+        let part = match ch.to_ascii_lowercase() {
+            'd' => Duration::try_days(value),
+            'h' => Duration::try_hours(value),
+            'm' => Duration::try_minutes(value),
+            's' => Duration::try_seconds(value),
+            _ => return Err(invalid()),
+        };
+        total = part
+            .and_then(|part| total.checked_add(&part))
+            .ok_or_else(too_long)?;
+        seen_unit = true;
+    }
+    if !digits.is_empty() {
+        // A bare number is read as minutes, the most common unit for corrections.
+        let value: i64 = digits.parse().map_err(|_| invalid())?;
+        total = Duration::try_minutes(value)
+            .and_then(|part| total.checked_add(&part))
+            .ok_or_else(too_long)?;
+        seen_unit = true;
+    }
+    if !seen_unit {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+pub fn parse_date_input(input: &str, label: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(input.trim(), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid {} date \"{}\". Use YYYY-MM-DD.", label, input))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -27,4 +264,101 @@ mod tests {
         assert_eq!(format_duration(60), "00:01:00");
         assert_eq!(format_duration(3661), "01:01:01");
     }
+
+    #[test]
+    fn parse_duration_input_accepts_compound_units() {
+        assert_eq!(parse_duration_input("15m").unwrap(), Duration::minutes(15));
+        assert_eq!(
+            parse_duration_input("1h30m").unwrap(),
+            Duration::minutes(90)
+        );
+        assert_eq!(parse_duration_input("90s").unwrap(), Duration::seconds(90));
+        assert_eq!(parse_duration_input("20").unwrap(), Duration::minutes(20));
+        assert!(parse_duration_input("").is_err());
+        assert!(parse_duration_input("h").is_err());
+        assert!(parse_duration_input("5x").is_err());
+        assert!(parse_duration_input("999999999999d").is_err());
+        assert!(parse_duration_input("60000000000d60000000000d").is_err());
+        let now = Utc.with_ymd_and_hms(2026, 10, 15, 12, 0, 0).unwrap();
+        assert!(resolve_event_time(None, Some("99999999999d"), now).is_err());
+    }
+
+    #[test]
+    fn parse_datetime_input_understands_local_and_relative_times() {
+        // Thursday 2026-10-15, 12:00 local time.
+        let local = |date: &str, time: &str| {
+            let naive =
+                NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M:%S")
+                    .unwrap();
+            local_to_utc(naive).unwrap()
+        };
+        let now = local("2026-10-15", "12:00:00");
+        let parse = |input: &str| parse_datetime_input(input, now, "test");
+
+        assert_eq!(parse("now").unwrap(), now);
+        assert_eq!(parse("14:30").unwrap(), local("2026-10-15", "14:30:00"));
+        assert_eq!(
+            parse("2026-10-14 09:00").unwrap(),
+            local("2026-10-14", "09:00:00")
+        );
+        assert_eq!(
+            parse("2026-10-14T09:00:30").unwrap(),
+            local("2026-10-14", "09:00:30")
+        );
+        assert_eq!(
+            parse("2026-10-14").unwrap(),
+            local("2026-10-14", "00:00:00")
+        );
+        assert_eq!(parse("-20m").unwrap(), now - Duration::minutes(20));
+        assert_eq!(parse("2h ago").unwrap(), now - Duration::hours(2));
+        assert_eq!(parse("1h 30m ago").unwrap(), now - Duration::minutes(90));
+        assert_eq!(
+            parse("yesterday 17:00").unwrap(),
+            local("2026-10-14", "17:00:00")
+        );
+        assert_eq!(
+            parse("monday 9am").unwrap(),
+            local("2026-10-12", "09:00:00")
+        );
+        assert_eq!(parse("Thu 12pm").unwrap(), local("2026-10-15", "12:00:00"));
+        assert_eq!(
+            parse("last thursday 12am").unwrap(),
+            local("2026-10-08", "00:00:00")
+        );
+        assert_eq!(
+            parse("2026-10-15T10:00:00Z").unwrap(),
+            Utc.with_ymd_and_hms(2026, 10, 15, 10, 0, 0).unwrap()
+        );
+        for invalid in [
+            "",
+            "9",
+            "25:00",
+            "13pm",
+            "someday 9am",
+            "ago",
+            "-99999999999d",
+            "+99999999999d",
+            "99999999999d ago",
+        ] {
+            assert!(parse(invalid).is_err(), "{} should be rejected", invalid);
+        }
+    }
+
+    #[test]
+    fn month_bounds_cover_whole_month() {
+        let date = NaiveDate::from_ymd_opt(2024, 2, 14).unwrap();
+        assert_eq!(
+            month_start(date),
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()
+        );
+        assert_eq!(
+            month_end(date),
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+        );
+        let december = NaiveDate::from_ymd_opt(2025, 12, 3).unwrap();
+        assert_eq!(
+            month_end(december),
+            NaiveDate::from_ymd_opt(2025, 12, 31).unwrap()
+        );
+    }
 }
@@ -0,0 +1,257 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+use crate::model::Store;
+use crate::time::format_datetime_local;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub task_id: String,
+    pub segment: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.segment {
+            Some(segment) => write!(
+                f,
+                "task {} segment {}: {}",
+                self.task_id, segment, self.message
+            ),
+            None => write!(f, "task {}: {}", self.task_id, self.message),
+        }
+    }
+}
+
+pub fn validate_store(store: &Store) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut open_segments = Vec::new();
+
+    for task in &store.tasks {
+        let violation = |segment: Option<usize>, message: String| Violation {
+            task_id: task.id.clone(),
+            segment,
+            message,
+        };
+        for (idx, segment) in task.segments.iter().enumerate() {
+            match segment.end_at {
+                Some(end_at) if end_at < segment.start_at => violations.push(violation(
+                    Some(idx + 1),
+                    format!(
+                        "ends at {} before it starts at {}",
+                        format_datetime_local(end_at),
+                        format_datetime_local(segment.start_at)
+                    ),
+                )),
+                Some(_) => {}
+                None if task.closed_at.is_some() => violations.push(violation(
+                    Some(idx + 1),
+                    "is still open but the task is closed".into(),
+                )),
+                None => open_segments.push((task.id.clone(), idx + 1)),
+            }
+        }
+        if let Some(closed_at) = task.closed_at {
+            let latest = task
+                .segments
+                .iter()
+                .flat_map(|segment| [Some(segment.start_at), segment.end_at])
+                .flatten()
+                .max();
+            if let Some(latest) = latest
+                && closed_at < latest
+            {
+                violations.push(violation(
+                    None,
+                    format!(
+                        "closed at {} before its last segment time {}",
+                        format_datetime_local(closed_at),
+                        format_datetime_local(latest)
+                    ),
+                ));
+            }
+        }
+    }
+
+    if open_segments.len() > 1 {
+        for (task_id, segment) in open_segments {
+            violations.push(Violation {
+                task_id,
+                segment: Some(segment),
+                message: "is one of several open segments (only one task can run at a time)".into(),
+            });
+        }
+    }
+
+    for (earlier, later) in overlapping_segments(store) {
+        violations.push(Violation {
+            task_id: store.tasks[later.task].id.clone(),
+            segment: Some(later.segment + 1),
+            message: format!(
+                "overlaps task {} segment {}",
+                store.tasks[earlier.task].id,
+                earlier.segment + 1
+            ),
+        });
+    }
+    violations
+}
+
+/// Zero-based position of a segment in the store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SegmentRef {
+    pub task: usize,
+    pub segment: usize,
+}
+
+/// Pairs of overlapping segments as (earlier, later), ignoring empty and inverted segments.
+pub fn overlapping_segments(store: &Store) -> Vec<(SegmentRef, SegmentRef)> {
+    struct Span {
+        at: SegmentRef,
+        start_at: DateTime<Utc>,
+        end_at: Option<DateTime<Utc>>,
+    }
+
+    let mut spans: Vec<Span> = store
+        .tasks
+        .iter()
+        .enumerate()
+        .flat_map(|(task_idx, task)| {
+            task.segments
+                .iter()
+                .enumerate()
+                .filter(|(_, segment)| segment.end_at.is_none_or(|end| end > segment.start_at))
+                .map(move |(idx, segment)| Span {
+                    at: SegmentRef {
+                        task: task_idx,
+                        segment: idx,
+                    },
+                    start_at: segment.start_at,
+                    end_at: segment.end_at,
+                })
+        })
+        .collect();
+    spans.sort_by_key(|span| span.start_at);
+
+    // Compare each segment with the one reaching furthest so far; open segments reach forever.
+    let mut pairs = Vec::new();
+    let mut furthest: Option<&Span> = None;
+    for span in &spans {
+        if let Some(previous) = furthest {
+            if previous.end_at.is_none_or(|end| end > span.start_at) {
+                pairs.push((previous.at, span.at));
+            }
+            let reaches_further = match (previous.end_at, span.end_at) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(previous_end), Some(end)) => end > previous_end,
+            };
+            if !reaches_further {
+                continue;
+            }
+        }
+        furthest = Some(span);
+    }
+    pairs
+}
+
+pub fn format_violations(violations: &[Violation]) -> String {
+    let mut message = format!("The data has {} consistency problem(s):", violations.len());
+    for violation in violations {
+        message.push_str("\n  - ");
+        message.push_str(&violation.to_string());
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+    use crate::model::{Segment, Task};
+
+    #[test]
+    fn validate_store_reports_each_invariant_violation() {
+        let nine = Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap();
+        let at = |minutes: i64| nine + Duration::minutes(minutes);
+        let segment = |start: i64, end: Option<i64>| Segment {
+            start_at: at(start),
+            end_at: end.map(at),
+        };
+        let task = |id: &str, closed_at: Option<i64>, segments: Vec<Segment>| Task {
+            id: id.into(),
+            name: id.into(),
+            tags: Vec::new(),
+            project_id: None,
+            archived: false,
+            created_at: nine,
+            closed_at: closed_at.map(at),
+            segments,
+        };
+
+        let valid = Store {
+            tasks: vec![
+                task(
+                    "a",
+                    Some(60),
+                    vec![segment(0, Some(30)), segment(45, Some(60))],
+                ),
+                task("b", None, vec![segment(60, None)]),
+            ],
+            ..Store::default()
+        };
+        assert!(validate_store(&valid).is_empty());
+
+        let invalid = Store {
+            tasks: vec![
+                task(
+                    "a",
+                    Some(20),
+                    vec![segment(0, Some(30)), segment(50, Some(40))],
+                ),
+                task("b", None, vec![segment(10, Some(20)), segment(100, None)]),
+                task("c", Some(200), vec![segment(120, None)]),
+                task("d", None, vec![segment(300, None)]),
+            ],
+            ..Store::default()
+        };
+        let messages: Vec<String> = validate_store(&invalid)
+            .iter()
+            .map(|violation| violation.to_string())
+            .collect();
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.starts_with("task a segment 2: ends at"))
+        );
+        assert!(messages.iter().any(|m| m.starts_with("task a: closed at")));
+        assert!(
+            messages
+                .iter()
+                .any(|m| m == "task c segment 1: is still open but the task is closed")
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.starts_with("task b segment 2: is one of several open"))
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.starts_with("task d segment 1: is one of several open"))
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|m| m == "task b segment 1: overlaps task a segment 1")
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|m| m == "task c segment 1: overlaps task b segment 2")
+        );
+    }
+}